@@ -1,3 +1,5 @@
+use glam::DMat4;
+
 use crate::{ray::Ray, vec3::Point3};
 
 #[derive(Clone, Copy)]
@@ -11,7 +13,7 @@ impl Aabb {
         Aabb { min, max }
     }
 
-    pub fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> bool {
+    pub fn hit(&self, r: &Ray, mut t_min: f64, mut t_max: f64) -> bool {
         for a in 0usize..=2 {
             let inv_d = 1f64 / r.direction[a];
             let mut t0 = (self.min[a] - r.origin[a]) * inv_d;
@@ -21,8 +23,9 @@ impl Aabb {
                 (t1, t0) = (t0, t1);
             }
 
-            let t_min = t0.max(t_min);
-            let t_max = t1.min(t_max);
+            // Narrow the interval slab by slab, the ray is only inside the box where all overlap
+            t_min = t0.max(t_min);
+            t_max = t1.min(t_max);
 
             if t_max <= t_min {
                 return false;
@@ -32,6 +35,35 @@ impl Aabb {
         true
     }
 
+    pub fn centroid(&self) -> Point3 {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let extent = self.max - self.min;
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+
+    /// Box around this one after `matrix` moves its eight corners
+    pub fn transformed(&self, matrix: DMat4) -> Aabb {
+        let mut min = Point3::splat(f64::INFINITY);
+        let mut max = Point3::splat(f64::NEG_INFINITY);
+
+        for i in 0..8 {
+            let corner = Point3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            );
+            let corner = matrix.transform_point3(corner);
+
+            min = min.min(corner);
+            max = max.max(corner);
+        }
+
+        Aabb::new(min, max)
+    }
+
     pub fn surrounding_box(box0: Aabb, box1: Aabb) -> Aabb {
         let small = Point3::new(
             box0.min.x.min(box1.min.x),
@@ -48,3 +80,33 @@ impl Aabb {
         Aabb::new(small, big)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::Vec3;
+
+    fn unit_box() -> Aabb {
+        Aabb::new(Point3::ZERO, Point3::ONE)
+    }
+
+    #[test]
+    fn hits_ray_through_box() {
+        let ray = Ray::new(Point3::new(-1.0, 0.5, 0.5), Vec3::X, 0.0);
+        assert!(unit_box().hit(&ray, 0.0, f64::INFINITY));
+    }
+
+    #[test]
+    fn misses_ray_whose_slabs_overlap_one_at_a_time() {
+        // Inside the x slab for t in [1, 2] and the y slab for t in [2.5, 3.5], so only
+        // the second axis shows the ray passes beside the box
+        let ray = Ray::new(Point3::new(-1.0, 3.5, 0.5), Vec3::new(1.0, -1.0, 0.0), 0.0);
+        assert!(!unit_box().hit(&ray, 0.0, f64::INFINITY));
+    }
+
+    #[test]
+    fn misses_box_outside_t_range() {
+        let ray = Ray::new(Point3::new(-1.0, 0.5, 0.5), Vec3::X, 0.0);
+        assert!(!unit_box().hit(&ray, 0.0, 0.5));
+    }
+}
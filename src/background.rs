@@ -0,0 +1,71 @@
+use std::f64::consts::PI;
+
+use crate::{
+    texture::{ColorSpace, Filter, ImageTexture, Texture, TextureError, WrapMode},
+    vec3::{Color, Point3, Vec3},
+};
+
+/// Radiance arriving along rays that escape the scene
+pub trait Background: Sync + Send {
+    fn value(&self, direction: Vec3) -> Color;
+}
+
+pub struct SolidBackground {
+    pub color: Color,
+}
+
+impl Background for SolidBackground {
+    fn value(&self, _direction: Vec3) -> Color {
+        self.color
+    }
+}
+
+/// Vertical gradient from the first book
+pub struct SkyBackground {
+    pub horizon: Color,
+    pub zenith: Color,
+}
+
+impl Default for SkyBackground {
+    fn default() -> Self {
+        SkyBackground {
+            horizon: Color::new(1.0, 1.0, 1.0),
+            zenith: Color::new(0.5, 0.7, 1.0),
+        }
+    }
+}
+
+impl Background for SkyBackground {
+    fn value(&self, direction: Vec3) -> Color {
+        let t = 0.5 * (direction.normalize().y + 1.0);
+        (1.0 - t) * self.horizon + t * self.zenith
+    }
+}
+
+/// Equirectangular (latitude-longitude) environment map, usually an HDR image
+pub struct EnvironmentMap {
+    image: ImageTexture,
+}
+
+impl EnvironmentMap {
+    /// Decoded like any image texture, so 8- and 16-bit images from sRGB and float ones as
+    /// linear radiance
+    pub fn open(path: &str) -> Result<Self, TextureError> {
+        let image = ImageTexture::open(path, ColorSpace::Srgb)?
+            .with_wrap(WrapMode::Repeat, WrapMode::Clamp)
+            .with_filter(Filter::Nearest);
+
+        Ok(EnvironmentMap { image })
+    }
+}
+
+impl Background for EnvironmentMap {
+    fn value(&self, direction: Vec3) -> Color {
+        // Same mapping as Sphere::get_sphere_uv, so a map looks the same on a sphere and as a sky
+        let d = direction.normalize();
+        let theta = (-d.y).acos();
+        let phi = (-d.z).atan2(d.x) + PI;
+
+        self.image.value(phi / (2.0 * PI), theta / PI, Point3::ZERO)
+    }
+}
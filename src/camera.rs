@@ -4,6 +4,144 @@ use crate::{
     vec3::{Point3, Vec3},
 };
 
+/// Parameters a `Camera` is built from, kept so the camera can be adjusted and rebuilt
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraSettings {
+    pub lookfrom: Point3,
+    pub lookat: Point3,
+    pub vup: Vec3,
+    pub vertical_fov: f64,
+    pub aspect_ratio: f64,
+    pub aperture: f64,
+    pub focus_dist: f64,
+    pub time0: f64,
+    pub time1: f64,
+}
+
+impl CameraSettings {
+    pub fn build(self) -> Camera {
+        let theta = self.vertical_fov.to_radians();
+        let h = (theta / 2.0).tan();
+        let viewport_height = 2.0 * h;
+        let viewport_width = self.aspect_ratio * viewport_height;
+
+        let w = (self.lookfrom - self.lookat).normalize();
+        let u = self.vup.cross(w).normalize();
+        let v = w.cross(u);
+
+        let origin = self.lookfrom;
+        let horizontal = self.focus_dist * viewport_width * u;
+        let vertical = self.focus_dist * viewport_height * v;
+        let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - self.focus_dist * w;
+
+        let lens_radius = self.aperture / 2.0;
+
+        Camera {
+            origin,
+            lower_left_corner,
+            horizontal,
+            vertical,
+            u,
+            v,
+            lens_radius,
+            time0: self.time0,
+            time1: self.time1,
+            settings: self,
+            keyframes: Vec::new(),
+        }
+    }
+
+    /// Camera that moves through `keyframes` (sorted by time) while its shutter is open,
+    /// starting from its pose at `time0`
+    pub fn build_animated(self, keyframes: Vec<CameraKeyframe>) -> Camera {
+        let start = CameraSettings::animated(&keyframes, self.time0).build();
+
+        Camera { keyframes, ..start }
+    }
+
+    /// Move both the eye and the target, `forward` is along the view direction
+    pub fn translated(self, forward: f64, right: f64, up: f64) -> Self {
+        let w = (self.lookat - self.lookfrom).normalize();
+        let u = w.cross(self.vup).normalize();
+        let offset = forward * w + right * u + up * self.vup.normalize();
+
+        CameraSettings {
+            lookfrom: self.lookfrom + offset,
+            lookat: self.lookat + offset,
+            ..self
+        }
+    }
+
+    /// Turn the view about the eye, angles in degrees
+    pub fn rotated(self, yaw: f64, pitch: f64) -> Self {
+        let up = self.vup.normalize();
+        let view = self.lookat - self.lookfrom;
+        let distance = view.length();
+        let w = view / distance;
+
+        // Keep away from the poles so the basis stays well defined
+        let current_pitch = w.dot(up).clamp(-1.0, 1.0).asin().to_degrees();
+        let pitch = (current_pitch + pitch).clamp(-89.0, 89.0) - current_pitch;
+
+        let right = w.cross(up).normalize();
+        let w = rotate_about(w, up, -yaw.to_radians());
+        let w = rotate_about(
+            w,
+            rotate_about(right, up, -yaw.to_radians()),
+            pitch.to_radians(),
+        );
+
+        CameraSettings {
+            lookat: self.lookfrom + distance * w,
+            ..self
+        }
+    }
+
+    /// Blend the pose towards `other`, shutter times and aspect ratio stay as they are
+    pub fn lerp(self, other: CameraSettings, s: f64) -> Self {
+        let mix = |a: f64, b: f64| a + s * (b - a);
+
+        CameraSettings {
+            lookfrom: self.lookfrom.lerp(other.lookfrom, s),
+            lookat: self.lookat.lerp(other.lookat, s),
+            vup: self.vup.lerp(other.vup, s),
+            vertical_fov: mix(self.vertical_fov, other.vertical_fov),
+            aperture: mix(self.aperture, other.aperture),
+            focus_dist: mix(self.focus_dist, other.focus_dist),
+            ..self
+        }
+    }
+
+    /// Pose at `time` along `keyframes` (sorted by time), holding still before the first
+    /// and after the last
+    pub fn animated(keyframes: &[CameraKeyframe], time: f64) -> Self {
+        let next = keyframes.partition_point(|keyframe| keyframe.time <= time);
+
+        match next {
+            0 => keyframes[0].settings,
+            n if n == keyframes.len() => keyframes[n - 1].settings,
+            n => {
+                let (a, b) = (&keyframes[n - 1], &keyframes[n]);
+                a.settings
+                    .lerp(b.settings, (time - a.time) / (b.time - a.time))
+            }
+        }
+    }
+}
+
+/// Camera pose at one moment of an animation
+#[derive(Clone, Copy, Debug)]
+pub struct CameraKeyframe {
+    pub time: f64,
+    pub settings: CameraSettings,
+}
+
+/// Rodrigues' rotation of `v` about unit `axis`
+fn rotate_about(v: Vec3, axis: Vec3, angle: f64) -> Vec3 {
+    let (sin, cos) = angle.sin_cos();
+    v * cos + axis.cross(v) * sin + axis * axis.dot(v) * (1.0 - cos)
+}
+
 pub struct Camera {
     origin: Point3,
     lower_left_corner: Point3,
@@ -14,9 +152,13 @@ pub struct Camera {
     lens_radius: f64,
     time0: f64,
     time1: f64,
+    pub settings: CameraSettings,
+    /// Poses to move through, empty for a camera that holds still
+    keyframes: Vec<CameraKeyframe>,
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         lookfrom: Point3,
         lookat: Point3,
@@ -28,42 +170,40 @@ impl Camera {
         time0: f64,
         time1: f64,
     ) -> Camera {
-        let theta = vertical_fov.to_radians();
-        let h = (theta / 2.0).tan();
-        let viewport_height = 2.0 * h;
-        let viewport_width = aspect_ratio * viewport_height;
-
-        let w = (lookfrom - lookat).normalize();
-        let u = vup.cross(w).normalize();
-        let v = w.cross(u);
-
-        let origin = lookfrom;
-        let horizontal = focus_dist * viewport_width * u;
-        let vertical = focus_dist * viewport_height * v;
-        let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - focus_dist * w;
-
-        let lens_radius = aperture / 2.0;
-
-        Camera {
-            origin,
-            lower_left_corner,
-            horizontal,
-            vertical,
-            u,
-            v,
-            lens_radius,
+        CameraSettings {
+            lookfrom,
+            lookat,
+            vup,
+            vertical_fov,
+            aspect_ratio,
+            aperture,
+            focus_dist,
             time0,
             time1,
         }
+        .build()
     }
+
     pub fn get_ray(&self, s: f64, t: f64) -> Ray {
+        let time = random_double(self.time0, self.time1);
+        if self.keyframes.is_empty() {
+            return self.ray_at(s, t, time);
+        }
+
+        // Posed at the ray's own time, so the camera's motion blurs too
+        CameraSettings::animated(&self.keyframes, time)
+            .build()
+            .ray_at(s, t, time)
+    }
+
+    fn ray_at(&self, s: f64, t: f64, time: f64) -> Ray {
         let rd = self.lens_radius * random_in_unit_disc();
         let offset = self.u * rd.x + self.v * rd.y;
 
         Ray::new(
             self.origin + offset,
             self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
-            random_double(self.time0, self.time1),
+            time,
         )
     }
 }
@@ -0,0 +1,434 @@
+use std::{collections::HashMap, error::Error, sync::Arc};
+
+use glam::DMat4;
+use gltf::{
+    camera::Projection,
+    image::Format,
+    mesh::Mode,
+    texture::{MagFilter, WrappingMode},
+    Node,
+};
+use image::{DynamicImage, ImageBuffer};
+
+use crate::{
+    camera::Camera,
+    hittable::{
+        bvh::BvhBuilder,
+        cutout::{AlphaMode, Cutout},
+        hittable_list::HittableList,
+        triangle::Triangle,
+        triangle_mesh::TriangleMesh,
+        Hittable,
+    },
+    material::{DiffuseLight, Material, Microfacet, NormalMap},
+    texture::{
+        ChannelTexture, ColorSpace, Filter, ImageTexture, SolidColor, Texture, TintedTexture,
+        WrapMode,
+    },
+    vec3::{Color, Point3, Vec3},
+};
+
+/// Everything rendered from a glTF file, with node transforms baked into the triangles
+pub struct GltfScene {
+    pub objects: TriangleMesh,
+    /// The first perspective camera in the file's list of cameras that a node in the scene
+    /// uses, if there is one
+    pub camera: Option<Camera>,
+}
+
+/// Load the default scene (or the first) of a .gltf or .glb file
+pub fn load_gltf(
+    path: &str,
+    aspect_ratio: f64,
+    bvh: BvhBuilder,
+) -> Result<GltfScene, Box<dyn Error>> {
+    let (document, buffers, images) = gltf::import(path)?;
+    let mut importer = Importer {
+        buffers: &buffers,
+        images: &images,
+        materials: HashMap::new(),
+        masks: HashMap::new(),
+        triangles: HittableList::new(),
+        camera: None,
+        aspect_ratio,
+    };
+
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| format!("No scenes in {}", path))?;
+
+    for node in scene.nodes() {
+        importer.visit(&node, DMat4::IDENTITY)?;
+    }
+
+    if importer.triangles.is_empty() {
+        return Err(format!("No triangles in {}", path).into());
+    }
+
+    Ok(GltfScene {
+        objects: TriangleMesh::new(importer.triangles, bvh),
+        camera: importer.camera.map(|(_, camera)| camera),
+    })
+}
+
+/// Opacity of a masked or blended material
+#[derive(Clone)]
+struct Mask {
+    opacity: Arc<dyn Texture>,
+    mode: AlphaMode,
+}
+
+struct Importer<'a> {
+    buffers: &'a [gltf::buffer::Data],
+    images: &'a [gltf::image::Data],
+    /// Converted materials by glTF index, `None` is the default material
+    materials: HashMap<Option<usize>, Arc<dyn Material>>,
+    /// Opacity of each material, `None` for opaque ones
+    masks: HashMap<Option<usize>, Option<Mask>>,
+    triangles: HittableList,
+    /// Camera with the lowest index found so far, and that index
+    camera: Option<(usize, Camera)>,
+    aspect_ratio: f64,
+}
+
+impl Importer<'_> {
+    fn visit(&mut self, node: &Node, parent: DMat4) -> Result<(), Box<dyn Error>> {
+        let local = node
+            .transform()
+            .matrix()
+            .map(|column| column.map(f64::from));
+        let transform = parent * DMat4::from_cols_array_2d(&local);
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                self.add_primitive(&primitive, transform)?;
+            }
+        }
+
+        // Nodes aren't visited in the order of the cameras they use, a camera placed by
+        // several nodes is seen from the first one visited
+        if let Some(camera) = node.camera() {
+            let found_earlier = matches!(self.camera, Some((index, _)) if index <= camera.index());
+            if !found_earlier {
+                if let Projection::Perspective(perspective) = camera.projection() {
+                    let converted = self.convert_camera(perspective.yfov() as f64, transform);
+                    self.camera = Some((camera.index(), converted));
+                }
+            }
+        }
+
+        for child in node.children() {
+            self.visit(&child, transform)?;
+        }
+
+        Ok(())
+    }
+
+    fn add_primitive(
+        &mut self,
+        primitive: &gltf::Primitive,
+        transform: DMat4,
+    ) -> Result<(), Box<dyn Error>> {
+        if primitive.mode() != Mode::Triangles {
+            return Ok(());
+        }
+
+        let material = self.material(&primitive.material())?;
+        let mask = self.mask(&primitive.material())?;
+        let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+
+        let positions: Vec<Point3> = match reader.read_positions() {
+            Some(positions) => positions
+                .map(|p| transform.transform_point3(Point3::from(p.map(f64::from))))
+                .collect(),
+            None => return Ok(()),
+        };
+
+        // Normals transform by the inverse transpose to stay perpendicular under scaling
+        let normal_matrix = transform.inverse().transpose();
+        let normals: Option<Vec<Vec3>> = reader.read_normals().map(|normals| {
+            normals
+                .map(|n| {
+                    normal_matrix
+                        .transform_vector3(Vec3::from(n.map(f64::from)))
+                        .normalize()
+                })
+                .collect()
+        });
+
+        // glTF puts the texture origin at the top left, ours is the bottom left
+        let uvs: Option<Vec<(f64, f64)>> = reader.read_tex_coords(0).map(|uvs| {
+            uvs.into_f32()
+                .map(|[u, v]| (u as f64, 1.0 - v as f64))
+                .collect()
+        });
+
+        let indices: Vec<usize> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+            None => (0..positions.len()).collect(),
+        };
+
+        // Checked up front so a malformed file is an error rather than a panic below
+        let vertex_count = positions.len();
+        if normals
+            .as_ref()
+            .is_some_and(|normals| normals.len() != vertex_count)
+            || uvs.as_ref().is_some_and(|uvs| uvs.len() != vertex_count)
+        {
+            return Err("Primitive attributes have different numbers of vertices".into());
+        }
+        if let Some(index) = indices.iter().find(|&&index| index >= vertex_count) {
+            return Err(format!(
+                "Primitive index {} is past its {} vertices",
+                index, vertex_count
+            )
+            .into());
+        }
+
+        for face in indices.chunks_exact(3) {
+            let [a, b, c] = [face[0], face[1], face[2]];
+            let mut triangle = Triangle::new(
+                [positions[a], positions[b], positions[c]],
+                Arc::clone(&material),
+            );
+
+            if let Some(normals) = &normals {
+                triangle = triangle.with_normals([normals[a], normals[b], normals[c]]);
+            }
+            if let Some(uvs) = &uvs {
+                triangle = triangle.with_uvs([uvs[a], uvs[b], uvs[c]]);
+            }
+
+            match &mask {
+                Some(mask) => self.triangles.push(Box::new(Cutout::new(
+                    Box::new(triangle),
+                    Arc::clone(&mask.opacity),
+                    mask.mode,
+                ))),
+                None => self.triangles.push(Box::new(triangle)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Emissive materials become `DiffuseLight`, the rest `Microfacet` which shares glTF's
+    /// metallic-roughness model
+    fn material(&mut self, material: &gltf::Material) -> Result<Arc<dyn Material>, Box<dyn Error>> {
+        if let Some(converted) = self.materials.get(&material.index()) {
+            return Ok(Arc::clone(converted));
+        }
+
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, _] = pbr.base_color_factor().map(f64::from);
+        let base_color = Color::new(r, g, b);
+
+        let emissive = Color::from(material.emissive_factor().map(f64::from))
+            * material.emissive_strength().unwrap_or(1.0) as f64;
+
+        // Textures are multiplied by their factors
+        let converted: Arc<dyn Material> = if emissive.max_element() > 0.0 {
+            let emit: Box<dyn Texture> = match material.emissive_texture() {
+                Some(info) => Box::new(TintedTexture::new(
+                    self.texture(&info.texture(), ColorSpace::Srgb)?,
+                    emissive,
+                )),
+                None => Box::new(SolidColor { color: emissive }),
+            };
+
+            Arc::new(DiffuseLight::textured(emit))
+        } else {
+            let base_color: Box<dyn Texture> = match pbr.base_color_texture() {
+                Some(info) => Box::new(TintedTexture::new(
+                    self.texture(&info.texture(), ColorSpace::Srgb)?,
+                    base_color,
+                )),
+                None => Box::new(SolidColor { color: base_color }),
+            };
+
+            // Roughness is packed into the green channel and metalness into the blue one
+            let metallic_roughness = |channel: usize, factor: f32| -> Result<_, Box<dyn Error>> {
+                let texture: Box<dyn Texture> = match pbr.metallic_roughness_texture() {
+                    Some(info) => Box::new(ChannelTexture::new(
+                        self.texture(&info.texture(), ColorSpace::Linear)?,
+                        channel,
+                        factor as f64,
+                    )),
+                    None => Box::new(SolidColor::splat(factor as f64)),
+                };
+                Ok(texture)
+            };
+
+            let microfacet = Arc::new(Microfacet::textured(
+                base_color,
+                metallic_roughness(2, pbr.metallic_factor())?,
+                metallic_roughness(1, pbr.roughness_factor())?,
+            ));
+
+            match material.normal_texture() {
+                Some(normal) => Arc::new(NormalMap::new(
+                    microfacet,
+                    self.texture(&normal.texture(), ColorSpace::Linear)?,
+                    normal.scale() as f64,
+                )),
+                None => microfacet,
+            }
+        };
+
+        self.materials
+            .insert(material.index(), Arc::clone(&converted));
+        Ok(converted)
+    }
+
+    /// Opacity comes from the alpha of the base color texture, materials without one are
+    /// left opaque. Blending becomes stochastic transparency, ignoring the base color factor.
+    fn mask(&mut self, material: &gltf::Material) -> Result<Option<Mask>, Box<dyn Error>> {
+        if let Some(mask) = self.masks.get(&material.index()) {
+            return Ok(mask.clone());
+        }
+
+        let pbr = material.pbr_metallic_roughness();
+        let mode = match material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => None,
+            // Opacity is the texture's alpha times the factor's
+            gltf::material::AlphaMode::Mask => Some(AlphaMode::Threshold(
+                material.alpha_cutoff().unwrap_or(0.5) as f64 / pbr.base_color_factor()[3] as f64,
+            )),
+            gltf::material::AlphaMode::Blend => Some(AlphaMode::Stochastic),
+        };
+
+        let mask = match (mode, pbr.base_color_texture()) {
+            (Some(mode), Some(info)) => Some(Mask {
+                opacity: Arc::from(self.texture(&info.texture(), ColorSpace::Srgb)?),
+                mode,
+            }),
+            _ => None,
+        };
+
+        self.masks.insert(material.index(), mask.clone());
+        Ok(mask)
+    }
+
+    /// Color textures are sRGB encoded and data textures linear, as the spec requires
+    fn texture(
+        &self,
+        texture: &gltf::Texture,
+        color_space: ColorSpace,
+    ) -> Result<Box<dyn Texture>, Box<dyn Error>> {
+        let image = decode_image(&self.images[texture.source().index()])?;
+
+        let sampler = texture.sampler();
+        let filter = match sampler.mag_filter() {
+            Some(MagFilter::Nearest) => Filter::Nearest,
+            _ => Filter::Bilinear,
+        };
+
+        let texture = ImageTexture::from_image(image, color_space)
+            .with_wrap(wrap_mode(sampler.wrap_s()), wrap_mode(sampler.wrap_t()))
+            .with_filter(filter);
+
+        Ok(Box::new(texture))
+    }
+
+    /// glTF cameras look down their local -z with +y up
+    fn convert_camera(&self, yfov: f64, transform: DMat4) -> Camera {
+        let lookfrom = transform.transform_point3(Point3::ZERO);
+        let forward = transform.transform_vector3(-Vec3::Z).normalize();
+        let vup = transform.transform_vector3(Vec3::Y).normalize();
+
+        Camera::new(
+            lookfrom,
+            lookfrom + forward,
+            vup,
+            yfov.to_degrees(),
+            self.aspect_ratio,
+            0.0,
+            1.0,
+            0.0,
+            1.0,
+        )
+    }
+}
+
+/// Rebuild glTF's decoded pixels as an image, 16-bit and float channels are stored in
+/// native byte order
+fn decode_image(image: &gltf::image::Data) -> Result<DynamicImage, Box<dyn Error>> {
+    let (width, height) = (image.width, image.height);
+    let bytes = image.pixels.clone();
+    let wide = || -> Vec<u16> {
+        image
+            .pixels
+            .chunks_exact(2)
+            .map(|pair| u16::from_ne_bytes([pair[0], pair[1]]))
+            .collect()
+    };
+    let float = || -> Vec<f32> {
+        image
+            .pixels
+            .chunks_exact(4)
+            .map(|quad| f32::from_ne_bytes([quad[0], quad[1], quad[2], quad[3]]))
+            .collect()
+    };
+
+    let decoded = match image.format {
+        Format::R8 => ImageBuffer::from_raw(width, height, bytes).map(DynamicImage::ImageLuma8),
+        Format::R8G8 => ImageBuffer::from_raw(width, height, bytes).map(DynamicImage::ImageLumaA8),
+        Format::R8G8B8 => ImageBuffer::from_raw(width, height, bytes).map(DynamicImage::ImageRgb8),
+        Format::R8G8B8A8 => {
+            ImageBuffer::from_raw(width, height, bytes).map(DynamicImage::ImageRgba8)
+        }
+        Format::R16 => ImageBuffer::from_raw(width, height, wide()).map(DynamicImage::ImageLuma16),
+        Format::R16G16 => {
+            ImageBuffer::from_raw(width, height, wide()).map(DynamicImage::ImageLumaA16)
+        }
+        Format::R16G16B16 => {
+            ImageBuffer::from_raw(width, height, wide()).map(DynamicImage::ImageRgb16)
+        }
+        Format::R16G16B16A16 => {
+            ImageBuffer::from_raw(width, height, wide()).map(DynamicImage::ImageRgba16)
+        }
+        Format::R32G32B32FLOAT => {
+            ImageBuffer::from_raw(width, height, float()).map(DynamicImage::ImageRgb32F)
+        }
+        Format::R32G32B32A32FLOAT => {
+            ImageBuffer::from_raw(width, height, float()).map(DynamicImage::ImageRgba32F)
+        }
+    };
+
+    decoded
+        .ok_or_else(|| format!("Texture data doesn't match its {:?} format", image.format).into())
+}
+
+fn wrap_mode(wrapping: WrappingMode) -> WrapMode {
+    match wrapping {
+        WrappingMode::Repeat => WrapMode::Repeat,
+        WrappingMode::MirroredRepeat => WrapMode::Mirror,
+        WrappingMode::ClampToEdge => WrapMode::Clamp,
+    }
+}
+
+/// Camera looking at the whole of `objects` from +z, for files without a camera
+pub fn framing_camera(objects: &dyn Hittable, aspect_ratio: f64) -> Camera {
+    let vfov: f64 = 40.0;
+    let (centre, radius) = match objects.bounding_box(0.0, 1.0) {
+        Some(bbox) => (
+            (bbox.min + bbox.max) / 2.0,
+            (bbox.max - bbox.min).length() / 2.0,
+        ),
+        None => (Point3::ZERO, 1.0),
+    };
+    let distance = radius / (vfov.to_radians() / 2.0).sin();
+
+    Camera::new(
+        centre + Vec3::new(0.0, 0.0, distance),
+        centre,
+        Vec3::Y,
+        vfov,
+        aspect_ratio,
+        0.0,
+        distance,
+        0.0,
+        1.0,
+    )
+}
@@ -3,18 +3,25 @@ use std::sync::Arc;
 use crate::{
     aabb::Aabb,
     material::Material,
+    onb::Onb,
     ray::Ray,
     vec3::{Point3, Vec3},
 };
 
+pub mod animated_transform;
 pub mod box_rect;
 pub mod bvh;
 pub mod constant_medium;
+pub mod cutout;
+pub mod flat_bvh;
 pub mod hittable_list;
 pub mod moving_sphere;
 pub mod rotate_y;
 pub mod sphere;
+pub mod transform;
 pub mod translate;
+pub mod triangle;
+pub mod triangle_mesh;
 pub mod xy_rect;
 pub mod xz_rect;
 pub mod yz_rect;
@@ -27,11 +34,44 @@ pub struct HitRecord {
     pub front_face: bool,
     pub u: f64,
     pub v: f64,
+    /// How the point moves with u and v, tangent to the surface. Used to orient
+    /// bump and normal maps.
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
 }
 
 pub trait Hittable: Sync + Send {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
     fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb>;
+
+    /// Density of sampling `direction` from `origin` at `time` when aiming at this object
+    fn pdf_value(&self, _origin: Point3, _direction: Vec3, _time: f64) -> f64 {
+        0.0
+    }
+
+    /// Random direction from `origin` towards this object as it is at `time`
+    fn random(&self, _origin: Point3, _time: f64) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+}
+
+/// Lets one object be in both the world and the lights without building it twice
+impl<T: Hittable + ?Sized> Hittable for Arc<T> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        (**self).hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        (**self).bounding_box(time0, time1)
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        (**self).pdf_value(origin, direction, time)
+    }
+
+    fn random(&self, origin: Point3, time: f64) -> Vec3 {
+        (**self).random(origin, time)
+    }
 }
 
 impl HitRecord {
@@ -50,6 +90,11 @@ impl HitRecord {
             false => -outward_normal,
         };
 
+        // Any tangents will do until the hittable sets its own
+        let Onb {
+            u: dpdu, v: dpdv, ..
+        } = Onb::build_from_w(outward_normal);
+
         HitRecord {
             point,
             t,
@@ -58,6 +103,37 @@ impl HitRecord {
             front_face,
             u,
             v,
+            dpdu,
+            dpdv,
+        }
+    }
+
+    pub fn with_tangents(mut self, dpdu: Vec3, dpdv: Vec3) -> Self {
+        self.dpdu = dpdu;
+        self.dpdv = dpdv;
+        self
+    }
+
+    /// The normal on the outside of the surface, whichever side was hit
+    pub fn outward_normal(&self) -> Vec3 {
+        match self.front_face {
+            true => self.normal,
+            false => -self.normal,
         }
     }
 }
+
+/// Plain gray material for tests that only care about geometry
+#[cfg(test)]
+pub fn test_material() -> Arc<dyn Material> {
+    use crate::{material::Lambertian, texture::SolidColor};
+
+    Arc::new(Lambertian {
+        albedo: Box::new(SolidColor::new(0.5, 0.5, 0.5)),
+    })
+}
+
+#[cfg(test)]
+pub fn test_sphere(centre: Point3, radius: f64) -> sphere::Sphere {
+    sphere::Sphere::new(centre, radius, test_material())
+}
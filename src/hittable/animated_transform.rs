@@ -0,0 +1,227 @@
+use std::sync::Arc;
+
+use glam::{DMat3, DMat4, DQuat};
+
+use crate::{
+    aabb::Aabb,
+    ray::Ray,
+    vec3::{Point3, Vec3},
+};
+
+use super::{transform::solid_angle_scale, HitRecord, Hittable};
+
+/// Pose of an object at `time`, applied as scale, then rotation, then translation
+#[derive(Clone, Copy, Debug)]
+pub struct Keyframe {
+    pub time: f64,
+    pub translation: Vec3,
+    pub rotation: DQuat,
+    pub scale: Vec3,
+}
+
+impl Keyframe {
+    pub fn new(time: f64, translation: Vec3, rotation: DQuat, scale: Vec3) -> Self {
+        Keyframe {
+            time,
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    fn matrix(&self) -> DMat4 {
+        DMat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+
+    /// Translation and scale move linearly, rotation along the shortest arc
+    fn interpolate(&self, next: &Keyframe, time: f64) -> Keyframe {
+        let s = (time - self.time) / (next.time - self.time);
+
+        Keyframe {
+            time,
+            translation: self.translation.lerp(next.translation, s),
+            rotation: self.rotation.slerp(next.rotation, s),
+            scale: self.scale.lerp(next.scale, s),
+        }
+    }
+}
+
+/// Object moving through keyframes over time, so anything can be motion blurred.
+/// Before the first and after the last keyframe the object holds still.
+pub struct AnimatedTransform {
+    object: Arc<dyn Hittable>,
+    keyframes: Vec<Keyframe>,
+}
+
+impl AnimatedTransform {
+    pub fn new(object: Box<dyn Hittable>, keyframes: Vec<Keyframe>) -> Self {
+        Self::instance(Arc::from(object), keyframes)
+    }
+
+    pub fn instance(object: Arc<dyn Hittable>, mut keyframes: Vec<Keyframe>) -> Self {
+        assert!(!keyframes.is_empty(), "AnimatedTransform needs a keyframe");
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        AnimatedTransform { object, keyframes }
+    }
+
+    fn pose(&self, time: f64) -> Keyframe {
+        let next = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.time <= time);
+
+        match next {
+            0 => self.keyframes[0],
+            n if n == self.keyframes.len() => self.keyframes[n - 1],
+            n => self.keyframes[n - 1].interpolate(&self.keyframes[n], time),
+        }
+    }
+
+    /// Poses at `time0`, `time1` and every keyframe between, the object moves
+    /// between consecutive ones by plain interpolation
+    fn poses(&self, time0: f64, time1: f64) -> Vec<Keyframe> {
+        let mut poses = vec![self.pose(time0)];
+        poses.extend(
+            self.keyframes
+                .iter()
+                .filter(|keyframe| keyframe.time > time0 && keyframe.time < time1),
+        );
+        poses.push(self.pose(time1));
+
+        poses
+    }
+}
+
+impl Hittable for AnimatedTransform {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let object_to_world = self.pose(ray.time).matrix();
+        let world_to_object = object_to_world.inverse();
+
+        let object_ray = Ray::new(
+            world_to_object.transform_point3(ray.origin),
+            world_to_object.transform_vector3(ray.direction),
+            ray.time,
+        );
+
+        let hit_record = self.object.hit(&object_ray, t_min, t_max)?;
+        let normal_matrix = DMat3::from_mat4(world_to_object).transpose();
+
+        Some(HitRecord {
+            point: object_to_world.transform_point3(hit_record.point),
+            normal: (normal_matrix * hit_record.normal).normalize(),
+            dpdu: object_to_world.transform_vector3(hit_record.dpdu),
+            dpdv: object_to_world.transform_vector3(hit_record.dpdv),
+            ..hit_record
+        })
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        let bbox = self.object.bounding_box(time0, time1)?;
+
+        self.poses(time0, time1)
+            .windows(2)
+            .map(|pair| swept_box(bbox, &pair[0], &pair[1]))
+            .reduce(Aabb::surrounding_box)
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        let world_to_object = self.pose(time).matrix().inverse();
+        let object_direction = world_to_object.transform_vector3(direction);
+        let density = self.object.pdf_value(
+            world_to_object.transform_point3(origin),
+            object_direction,
+            time,
+        );
+        let det = DMat3::from_mat4(world_to_object).determinant().abs();
+
+        density * solid_angle_scale(det, direction, object_direction)
+    }
+
+    fn random(&self, origin: Point3, time: f64) -> Vec3 {
+        let object_to_world = self.pose(time).matrix();
+        let object_origin = object_to_world.inverse().transform_point3(origin);
+
+        object_to_world.transform_vector3(self.object.random(object_origin, time))
+    }
+}
+
+/// Box around `bbox` for every pose between `start` and `end`
+fn swept_box(bbox: Aabb, start: &Keyframe, end: &Keyframe) -> Aabb {
+    // Without rotation every corner moves in a straight line, so the ends cover the sweep
+    if start.rotation.dot(end.rotation).abs() >= 1.0 - 1e-12 {
+        return Aabb::surrounding_box(
+            bbox.transformed(start.matrix()),
+            bbox.transformed(end.matrix()),
+        );
+    }
+
+    // Rotating corners swing out in arcs, but never further from the translation
+    // than the farthest scaled corner
+    let scale = start.scale.abs().max(end.scale.abs());
+    let radius = (bbox.min.abs().max(bbox.max.abs()) * scale).length();
+
+    Aabb::new(
+        start.translation.min(end.translation) - Vec3::splat(radius),
+        start.translation.max(end.translation) + Vec3::splat(radius),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_2;
+
+    use super::*;
+    use crate::hittable::test_sphere;
+
+    fn animated(keyframes: Vec<Keyframe>) -> AnimatedTransform {
+        AnimatedTransform::new(Box::new(test_sphere(Point3::ZERO, 1.0)), keyframes)
+    }
+
+    fn keyframes() -> Vec<Keyframe> {
+        vec![
+            Keyframe::new(2.0, Vec3::new(4.0, 0.0, 0.0), DQuat::IDENTITY, Vec3::ONE),
+            Keyframe::new(
+                4.0,
+                Vec3::new(8.0, 2.0, 0.0),
+                DQuat::from_rotation_y(FRAC_PI_2),
+                Vec3::splat(3.0),
+            ),
+        ]
+    }
+
+    #[test]
+    fn holds_still_outside_keyframes() {
+        let animated = animated(keyframes());
+
+        assert_eq!(animated.pose(0.0).translation, Vec3::new(4.0, 0.0, 0.0));
+        assert_eq!(animated.pose(9.0).translation, Vec3::new(8.0, 2.0, 0.0));
+        assert_eq!(animated.pose(9.0).scale, Vec3::splat(3.0));
+    }
+
+    #[test]
+    fn interpolates_between_keyframes() {
+        let pose = animated(keyframes()).pose(3.0);
+
+        assert!(pose
+            .translation
+            .abs_diff_eq(Vec3::new(6.0, 1.0, 0.0), 1e-12));
+        assert!(pose.scale.abs_diff_eq(Vec3::splat(2.0), 1e-12));
+
+        // Halfway along the shortest arc is half the angle about the same axis
+        let (axis, angle) = pose.rotation.to_axis_angle();
+        assert!(axis.abs_diff_eq(Vec3::Y, 1e-9));
+        assert!((angle - FRAC_PI_2 / 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn sorts_keyframes_by_time() {
+        let mut reversed = keyframes();
+        reversed.reverse();
+
+        let pose = animated(reversed).pose(2.5);
+
+        assert!(pose
+            .translation
+            .abs_diff_eq(Vec3::new(5.0, 0.5, 0.0), 1e-12));
+    }
+}
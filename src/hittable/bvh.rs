@@ -1,8 +1,29 @@
 use std::cmp::Ordering;
 
-use crate::{aabb::Aabb, ray::Ray, utils::random_double};
+use clap::ValueEnum;
 
-use super::{hittable_list::HittableList, Hittable};
+use crate::{aabb::Aabb, ray::Ray, utils::random_double, vec3::Point3};
+
+use super::{flat_bvh::FlatBvh, hittable_list::HittableList, Hittable};
+
+/// Number of centroid bins the SAH builder evaluates splits between, per axis
+const SAH_BINS: usize = 16;
+/// SAH builder leaves hold at most this many objects
+const MAX_LEAF_SIZE: usize = 4;
+/// Cost of visiting a node relative to intersecting one object
+const TRAVERSAL_COST: f64 = 0.125;
+
+/// How a `Bvh` partitions its objects between children
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum BvhBuilder {
+    /// Sort along a random axis and split at the median, one object per leaf, as in the book
+    Median,
+    /// Surface area heuristic over binned centroids, several objects per leaf
+    Sah,
+    /// Same splits as `Sah`, compiled into a `FlatBvh`
+    #[default]
+    Flat,
+}
 
 pub enum Bvh {
     TwinNode {
@@ -14,10 +35,41 @@ pub enum Bvh {
         only: Box<dyn Hittable>,
         bound: Aabb,
     },
+    Leaf {
+        objects: HittableList,
+        bound: Aabb,
+    },
 }
 
 impl Bvh {
-    pub fn new(mut objects: HittableList, time0: f64, time1: f64) -> Self {
+    pub fn new(objects: HittableList, time0: f64, time1: f64, builder: BvhBuilder) -> Self {
+        match builder {
+            BvhBuilder::Median => Self::build_median(objects, time0, time1),
+            BvhBuilder::Sah => {
+                let objects = objects
+                    .into_iter()
+                    .map(|object| {
+                        let bound = object
+                            .bounding_box(time0, time1)
+                            .expect("No bounding box in BvhNode constructor");
+                        (object, bound)
+                    })
+                    .collect();
+
+                Self::build_sah(objects)
+            }
+            BvhBuilder::Flat => {
+                let only = Box::new(FlatBvh::new(objects, time0, time1));
+                let bound = only
+                    .bounding_box(time0, time1)
+                    .expect("No objects in BvhNode constructor");
+
+                Bvh::SingNode { only, bound }
+            }
+        }
+    }
+
+    fn build_median(mut objects: HittableList, time0: f64, time1: f64) -> Self {
         // Why random here I wonder?
         let axis = random_double(0.0, 3.0).floor() as usize;
 
@@ -40,8 +92,8 @@ impl Bvh {
         let left_half = objects.drain(0..mid_index).collect();
         let right_half = objects;
 
-        let left = Box::new(Self::new(left_half, time0, time1));
-        let right = Box::new(Self::new(right_half, time0, time1));
+        let left = Box::new(Self::build_median(left_half, time0, time1));
+        let right = Box::new(Self::build_median(right_half, time0, time1));
 
         let left_box = left.bounding_box(time0, time1);
         let right_box = right.bounding_box(time0, time1);
@@ -54,6 +106,40 @@ impl Bvh {
         panic!("No bounding box in BvhNode constructor");
     }
 
+    /// Objects are paired with their bounding boxes so they're only computed once
+    fn build_sah(mut objects: Vec<(Box<dyn Hittable>, Aabb)>) -> Self {
+        let bound = objects
+            .iter()
+            .map(|(_, bound)| *bound)
+            .reduce(Aabb::surrounding_box)
+            .expect("No objects in BvhNode constructor");
+
+        if objects.len() == 1 {
+            let (only, bound) = objects.pop().unwrap();
+            return Bvh::SingNode { only, bound };
+        }
+
+        let bounds = objects.iter().map(|(_, bound)| *bound);
+        let (left_half, right_half) = match sah_partition(bounds, bound) {
+            SahPartition::Leaf => {
+                let objects = objects.into_iter().map(|(object, _)| object).collect();
+                return Bvh::Leaf { objects, bound };
+            }
+            SahPartition::Split(split) => objects
+                .into_iter()
+                .partition(|(_, bound)| split.goes_left(bound.centroid())),
+            SahPartition::Halves => {
+                let right_half = objects.split_off(objects.len() / 2);
+                (objects, right_half)
+            }
+        };
+
+        let left = Box::new(Self::build_sah(left_half));
+        let right = Box::new(Self::build_sah(right_half));
+
+        Bvh::TwinNode { left, right, bound }
+    }
+
     fn box_compare(a: &dyn Hittable, b: &dyn Hittable, axis: usize) -> Ordering {
         let box_a = a.bounding_box(0.0, 0.0);
         let box_b = b.bounding_box(0.0, 0.0);
@@ -78,6 +164,126 @@ impl Bvh {
     }
 }
 
+/// Cheapest split between centroid bins on any axis, `None` when the centroids
+/// can't be separated
+fn best_sah_split(bounds: impl Iterator<Item = Aabb> + Clone, bound: Aabb) -> Option<SahSplit> {
+    let centroid_bound = bounds
+        .clone()
+        .map(|bound| Aabb::new(bound.centroid(), bound.centroid()))
+        .reduce(Aabb::surrounding_box)?;
+
+    let mut best: Option<SahSplit> = None;
+
+    for axis in 0..3 {
+        let min = centroid_bound.min[axis];
+        let extent = centroid_bound.max[axis] - min;
+        if extent <= 0.0 {
+            continue;
+        }
+
+        let mut counts = [0usize; SAH_BINS];
+        let mut bin_bounds: [Option<Aabb>; SAH_BINS] = [None; SAH_BINS];
+        for object_bound in bounds.clone() {
+            let bin = bin_index(object_bound.centroid()[axis], min, extent);
+            counts[bin] += 1;
+            bin_bounds[bin] = merge(bin_bounds[bin], Some(object_bound));
+        }
+
+        // Sweep from the right to get the area and count of everything past each split
+        let mut right_areas = [0.0; SAH_BINS];
+        let mut right_counts = [0usize; SAH_BINS];
+        let mut right_bound: Option<Aabb> = None;
+        let mut right_count = 0;
+        for bin in (1..SAH_BINS).rev() {
+            right_bound = merge(right_bound, bin_bounds[bin]);
+            right_count += counts[bin];
+            right_areas[bin] = right_bound.map_or(0.0, |bound| bound.surface_area());
+            right_counts[bin] = right_count;
+        }
+
+        let mut left_bound: Option<Aabb> = None;
+        let mut left_count = 0;
+        for split in 1..SAH_BINS {
+            left_bound = merge(left_bound, bin_bounds[split - 1]);
+            left_count += counts[split - 1];
+            if left_count == 0 || right_counts[split] == 0 {
+                continue;
+            }
+
+            let left_area = left_bound.map_or(0.0, |bound| bound.surface_area());
+            let cost = TRAVERSAL_COST
+                + (left_count as f64 * left_area + right_counts[split] as f64 * right_areas[split])
+                    / bound.surface_area();
+
+            if best.as_ref().is_none_or(|best| cost < best.cost) {
+                best = Some(SahSplit {
+                    cost,
+                    axis,
+                    split,
+                    min,
+                    extent,
+                });
+            }
+        }
+    }
+
+    best
+}
+
+/// What the SAH builders do with a node's objects
+pub(super) enum SahPartition {
+    /// Keep them together in a leaf
+    Leaf,
+    /// Put those whose centroids `goes_left` in the first child
+    Split(SahSplit),
+    /// Every centroid is in the same place, any split is as good as another, so halve them
+    Halves,
+}
+
+/// Partition objects with `bounds`, together bounded by `bound`, so both SAH builders
+/// make the same tree. Small nodes become leaves unless splitting them is cheaper.
+pub(super) fn sah_partition(
+    bounds: impl Iterator<Item = Aabb> + Clone,
+    bound: Aabb,
+) -> SahPartition {
+    let count = bounds.clone().count();
+    let split = best_sah_split(bounds, bound);
+
+    match split {
+        Some(split) if count > MAX_LEAF_SIZE || split.cost < count as f64 => {
+            SahPartition::Split(split)
+        }
+        None if count > MAX_LEAF_SIZE => SahPartition::Halves,
+        _ => SahPartition::Leaf,
+    }
+}
+
+/// Split between centroid bins `split - 1` and `split` along `axis`
+pub(super) struct SahSplit {
+    cost: f64,
+    pub axis: usize,
+    split: usize,
+    min: f64,
+    extent: f64,
+}
+
+impl SahSplit {
+    pub fn goes_left(&self, centroid: Point3) -> bool {
+        bin_index(centroid[self.axis], self.min, self.extent) < self.split
+    }
+}
+
+fn bin_index(value: f64, min: f64, extent: f64) -> usize {
+    (((value - min) / extent * SAH_BINS as f64) as usize).min(SAH_BINS - 1)
+}
+
+fn merge(a: Option<Aabb>, b: Option<Aabb>) -> Option<Aabb> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(Aabb::surrounding_box(a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
 impl Hittable for Bvh {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<super::HitRecord> {
         match self {
@@ -93,6 +299,13 @@ impl Hittable for Bvh {
                 right_hit.or(left_hit)
             }
             Bvh::SingNode { only, bound: _ } => only.hit(ray, t_min, t_max),
+            Bvh::Leaf { objects, bound } => {
+                if !bound.hit(ray, t_min, t_max) {
+                    return None;
+                }
+
+                objects.hit(ray, t_min, t_max)
+            }
         }
     }
 
@@ -104,6 +317,7 @@ impl Hittable for Bvh {
                 bound,
             } => Some(*bound),
             Bvh::SingNode { only: _, bound } => Some(*bound),
+            Bvh::Leaf { objects: _, bound } => Some(*bound),
         }
     }
 }
@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    ray::Ray,
+    texture::Texture,
+    utils::random_double,
+    vec3::{Point3, Vec3},
+};
+
+use super::{HitRecord, Hittable};
+
+/// How a `Cutout` turns opacity into holes
+#[derive(Clone, Copy, Debug)]
+pub enum AlphaMode {
+    /// Solid where the opacity reaches the threshold, a hole everywhere else
+    Threshold(f64),
+    /// Solid with a chance equal to the opacity, so partial transparency averages out
+    /// over samples
+    Stochastic,
+}
+
+/// Object with holes wherever a texture's alpha channel makes it transparent, for leaves,
+/// fences and decals. Rays carry on through the holes to whatever is behind.
+pub struct Cutout {
+    object: Box<dyn Hittable>,
+    /// Shared, so the triangles of one mesh don't each need a copy of the image
+    opacity: Arc<dyn Texture>,
+    mode: AlphaMode,
+}
+
+impl Cutout {
+    pub fn new(object: Box<dyn Hittable>, opacity: Arc<dyn Texture>, mode: AlphaMode) -> Self {
+        Cutout {
+            object,
+            opacity,
+            mode,
+        }
+    }
+
+    fn is_solid(&self, rec: &HitRecord) -> bool {
+        let alpha = self.opacity.alpha(rec.u, rec.v, rec.point);
+        match self.mode {
+            AlphaMode::Threshold(threshold) => alpha >= threshold,
+            AlphaMode::Stochastic => random_double(0.0, 1.0) < alpha,
+        }
+    }
+}
+
+impl Hittable for Cutout {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut t_min = t_min;
+        loop {
+            let hit_record = self.object.hit(ray, t_min, t_max)?;
+            if self.is_solid(&hit_record) {
+                return Some(hit_record);
+            }
+
+            // Look again just past the hole, the object may be hit further on
+            t_min = hit_record.t.next_up();
+        }
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        self.object.bounding_box(time0, time1)
+    }
+
+    // Lights with holes are sampled as if they were whole
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        self.object.pdf_value(origin, direction, time)
+    }
+
+    fn random(&self, origin: Point3, time: f64) -> Vec3 {
+        self.object.random(origin, time)
+    }
+}
@@ -0,0 +1,225 @@
+use crate::{aabb::Aabb, ray::Ray};
+
+use super::{
+    bvh::{sah_partition, SahPartition},
+    hittable_list::HittableList,
+    HitRecord, Hittable,
+};
+
+/// Deepest a `FlatBvh` gets, so traversal fits in a fixed size stack
+const MAX_DEPTH: usize = 64;
+
+/// Node of a `FlatBvh`. Leaves (`count > 0`) own `indices[offset..offset + count]`,
+/// interior nodes have their first child straight after them and their second at `offset`.
+struct FlatNode {
+    bound: Aabb,
+    offset: usize,
+    count: usize,
+    axis: usize,
+}
+
+/// SAH BVH compiled into contiguous arrays and traversed with an explicit stack, faster
+/// than `Bvh` for large lists of objects
+pub struct FlatBvh {
+    nodes: Vec<FlatNode>,
+    /// Objects in leaf order, pointing into `objects`
+    indices: Vec<usize>,
+    objects: HittableList,
+}
+
+impl FlatBvh {
+    pub fn new(objects: HittableList, time0: f64, time1: f64) -> Self {
+        let bounds: Vec<Aabb> = objects
+            .iter()
+            .map(|object| {
+                object
+                    .bounding_box(time0, time1)
+                    .expect("No bounding box in FlatBvh constructor")
+            })
+            .collect();
+
+        let mut bvh = FlatBvh {
+            nodes: Vec::with_capacity(2 * objects.len()),
+            indices: (0..objects.len()).collect(),
+            objects,
+        };
+
+        if !bvh.objects.is_empty() {
+            bvh.build(&bounds, 0, bvh.objects.len(), 0);
+        }
+
+        bvh
+    }
+
+    /// Add the node for `indices[start..end]` and everything under it
+    fn build(&mut self, bounds: &[Aabb], start: usize, end: usize, depth: usize) {
+        let object_bounds = self.indices[start..end].iter().map(|&index| bounds[index]);
+        let bound = object_bounds.clone().reduce(Aabb::surrounding_box).unwrap();
+
+        let count = end - start;
+        let node = self.nodes.len();
+        self.nodes.push(FlatNode {
+            bound,
+            offset: start,
+            count,
+            axis: 0,
+        });
+
+        if count == 1 || depth + 1 >= MAX_DEPTH {
+            return;
+        }
+
+        let (mid, axis) = match sah_partition(object_bounds, bound) {
+            SahPartition::Leaf => return,
+            SahPartition::Split(split) => {
+                let mut mid = start;
+                for i in start..end {
+                    if split.goes_left(bounds[self.indices[i]].centroid()) {
+                        self.indices.swap(i, mid);
+                        mid += 1;
+                    }
+                }
+                (mid, split.axis)
+            }
+            SahPartition::Halves => ((start + end) / 2, 0),
+        };
+
+        self.build(bounds, start, mid, depth + 1);
+        let second_child = self.nodes.len();
+        self.build(bounds, mid, end, depth + 1);
+
+        self.nodes[node] = FlatNode {
+            bound,
+            offset: second_child,
+            count: 0,
+            axis,
+        };
+    }
+}
+
+impl Hittable for FlatBvh {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut closest_hit: Option<HitRecord> = None;
+        let mut closest_so_far = t_max;
+
+        let mut stack = [0usize; MAX_DEPTH];
+        let mut stack_size = 0;
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+
+            // A lone object is as quick to test as its bounding box
+            if node.count == 1 || node.bound.hit(ray, t_min, closest_so_far) {
+                if node.count > 0 {
+                    for &index in &self.indices[node.offset..node.offset + node.count] {
+                        if let Some(hit_record) =
+                            self.objects[index].hit(ray, t_min, closest_so_far)
+                        {
+                            closest_so_far = hit_record.t;
+                            closest_hit = Some(hit_record);
+                        }
+                    }
+                } else {
+                    // Visit the child nearer the ray origin first, its hits cull the other
+                    let (near, far) = if ray.direction[node.axis] < 0.0 {
+                        (node.offset, current + 1)
+                    } else {
+                        (current + 1, node.offset)
+                    };
+
+                    stack[stack_size] = far;
+                    stack_size += 1;
+                    current = near;
+                    continue;
+                }
+            }
+
+            if stack_size == 0 {
+                break;
+            }
+            stack_size -= 1;
+            current = stack[stack_size];
+        }
+
+        closest_hit
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        self.nodes.first().map(|node| node.bound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::{
+        hittable::test_sphere,
+        vec3::{Point3, Vec3},
+    };
+
+    fn spheres(centres: &[Point3]) -> HittableList {
+        centres
+            .iter()
+            .map(|&centre| Box::new(test_sphere(centre, 0.3)) as Box<dyn Hittable>)
+            .collect()
+    }
+
+    fn random_point(rng: &mut StdRng, extent: f64) -> Point3 {
+        Point3::new(
+            rng.gen_range(-extent..extent),
+            rng.gen_range(-extent..extent),
+            rng.gen_range(-extent..extent),
+        )
+    }
+
+    /// The nearest hit of every ray must be the one a plain list finds
+    fn assert_matches_list(centres: &[Point3]) {
+        let list = spheres(centres);
+        let bvh = FlatBvh::new(spheres(centres), 0.0, 1.0);
+        let mut rng = StdRng::seed_from_u64(1);
+
+        for _ in 0..2000 {
+            // Aimed near an object so most rays hit something
+            let origin = random_point(&mut rng, 8.0);
+            let target = centres[rng.gen_range(0..centres.len())] + random_point(&mut rng, 0.5);
+            let direction = target - origin;
+            let ray = Ray::new(origin, direction, 0.0);
+
+            let expected = list.hit(&ray, 0.001, f64::INFINITY).map(|hit| hit.t);
+            let actual = bvh.hit(&ray, 0.001, f64::INFINITY).map(|hit| hit.t);
+            assert_eq!(actual, expected, "ray from {} along {}", origin, direction);
+        }
+    }
+
+    #[test]
+    fn matches_list_on_random_rays() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let centres: Vec<Point3> = (0..300).map(|_| random_point(&mut rng, 5.0)).collect();
+
+        assert_matches_list(&centres);
+    }
+
+    #[test]
+    fn matches_list_with_coincident_centroids() {
+        let mut centres = vec![Point3::new(1.0, 2.0, 3.0); 20];
+        centres.push(Point3::ZERO);
+
+        assert_matches_list(&centres);
+    }
+
+    #[test]
+    fn empty_bvh_hits_nothing() {
+        let bvh = FlatBvh::new(HittableList::new(), 0.0, 1.0);
+        let ray = Ray::new(Point3::ZERO, Vec3::X, 0.0);
+
+        assert!(bvh.hit(&ray, 0.001, f64::INFINITY).is_none());
+        assert!(bvh.bounding_box(0.0, 1.0).is_none());
+    }
+}
@@ -1,5 +1,11 @@
+use rand::Rng;
+
 use super::{HitRecord, Hittable};
-use crate::{aabb::Aabb, ray::Ray};
+use crate::{
+    aabb::Aabb,
+    ray::Ray,
+    vec3::{Point3, Vec3},
+};
 
 pub type HittableList = Vec<Box<dyn Hittable>>;
 
@@ -38,4 +44,16 @@ impl Hittable for HittableList {
 
         output_box
     }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        let weight = 1.0 / self.len() as f64;
+        self.iter()
+            .map(|object| weight * object.pdf_value(origin, direction, time))
+            .sum()
+    }
+
+    fn random(&self, origin: Point3, time: f64) -> Vec3 {
+        let index = rand::thread_rng().gen_range(0..self.len());
+        self[index].random(origin, time)
+    }
 }
@@ -37,9 +37,11 @@ impl MovingSphere {
         }
     }
 
+    /// Holds still at either end outside `time0` to `time1`, so an animation running past
+    /// them doesn't carry the sphere away
     pub fn centre(&self, time: f64) -> Point3 {
-        self.centre0
-            + ((time - self.time0) / (self.time1 - self.time0)) * (self.centre1 - self.centre0)
+        let s = ((time - self.time0) / (self.time1 - self.time0)).clamp(0.0, 1.0);
+        self.centre0 + s * (self.centre1 - self.centre0)
     }
 }
 
@@ -92,3 +94,18 @@ impl Hittable for MovingSphere {
         Some(Aabb::surrounding_box(box0, box1))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::test_material;
+
+    #[test]
+    fn holds_still_outside_its_times() {
+        let sphere = MovingSphere::new(Point3::ZERO, Point3::X, 0.0, 1.0, 1.0, test_material());
+
+        assert_eq!(sphere.centre(-1.0), Point3::ZERO);
+        assert_eq!(sphere.centre(0.5), Point3::new(0.5, 0.0, 0.0));
+        assert_eq!(sphere.centre(2.0), Point3::X);
+    }
+}
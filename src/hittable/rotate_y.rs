@@ -38,15 +38,18 @@ impl Hittable for RotateY {
             normal[2] =
                 -self.sin_theta * hit_record.normal[0] + self.cos_theta * hit_record.normal[2];
 
-            return Some(HitRecord::new(
-                p,
-                hit_record.t,
-                &rotated_ray,
-                normal,
-                hit_record.material,
-                hit_record.u,
-                hit_record.v,
-            ));
+            return Some(
+                HitRecord::new(
+                    p,
+                    hit_record.t,
+                    &rotated_ray,
+                    normal,
+                    hit_record.material,
+                    hit_record.u,
+                    hit_record.v,
+                )
+                .with_tangents(self.rotate(hit_record.dpdu), self.rotate(hit_record.dpdv)),
+            );
         }
 
         None
@@ -55,9 +58,37 @@ impl Hittable for RotateY {
     fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
         self.bbox
     }
+
+    // Rotations keep solid angles, so the object's density carries over unchanged
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        self.obj
+            .pdf_value(self.unrotate(origin), self.unrotate(direction), time)
+    }
+
+    fn random(&self, origin: Point3, time: f64) -> Vec3 {
+        self.rotate(self.obj.random(self.unrotate(origin), time))
+    }
 }
 
 impl RotateY {
+    /// Rotate a vector from object space into world space
+    fn rotate(&self, vector: Vec3) -> Vec3 {
+        Vec3::new(
+            self.cos_theta * vector.x + self.sin_theta * vector.z,
+            vector.y,
+            -self.sin_theta * vector.x + self.cos_theta * vector.z,
+        )
+    }
+
+    /// Rotate a vector from world space into object space
+    fn unrotate(&self, vector: Vec3) -> Vec3 {
+        Vec3::new(
+            self.cos_theta * vector.x - self.sin_theta * vector.z,
+            vector.y,
+            self.sin_theta * vector.x + self.cos_theta * vector.z,
+        )
+    }
+
     pub fn new(obj: Box<dyn Hittable>, angle: f64) -> Self {
         let radians = angle.to_radians();
         let sin_theta = radians.sin();
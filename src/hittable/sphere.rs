@@ -4,7 +4,9 @@ use super::{HitRecord, Hittable};
 use crate::{
     aabb::Aabb,
     material::Material,
+    onb::Onb,
     ray::Ray,
+    utils::random_to_sphere,
     vec3::{Point3, Vec3},
 };
 
@@ -34,6 +36,17 @@ impl Sphere {
 
         (phi / (2f64 * PI), theta / PI)
     }
+
+    /// Derivatives of a point `p` on the sphere, relative to its centre, along u and v
+    fn get_sphere_tangents(p: Vec3) -> (Vec3, Vec3) {
+        // Distance from the poles' axis, kept off zero so the poles still have tangents
+        let radius_xz = p.x.hypot(p.z).max(1e-9);
+
+        let dpdu = 2.0 * PI * Vec3::new(p.z, 0.0, -p.x);
+        let dpdv = PI * Vec3::new(-p.x * p.y / radius_xz, radius_xz, -p.y * p.z / radius_xz);
+
+        (dpdu, dpdv)
+    }
 }
 
 impl Hittable for Sphere {
@@ -60,16 +73,20 @@ impl Hittable for Sphere {
         let point = ray.at(t);
         let outward_normal = (point - self.centre) / self.radius;
         let (u, v) = Self::get_sphere_uv(outward_normal);
+        let (dpdu, dpdv) = Self::get_sphere_tangents(point - self.centre);
 
-        Some(HitRecord::new(
-            point,
-            t,
-            ray,
-            outward_normal,
-            Arc::clone(&self.material),
-            u,
-            v,
-        ))
+        Some(
+            HitRecord::new(
+                point,
+                t,
+                ray,
+                outward_normal,
+                Arc::clone(&self.material),
+                u,
+                v,
+            )
+            .with_tangents(dpdu, dpdv),
+        )
     }
 
     fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
@@ -80,4 +97,25 @@ impl Hittable for Sphere {
 
         Some(output_box)
     }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        if self
+            .hit(&Ray::new(origin, direction, time), 0.001, f64::INFINITY)
+            .is_none()
+        {
+            return 0.0;
+        }
+
+        let distance_squared = (self.centre - origin).length_squared();
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared).sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: Point3, _time: f64) -> Vec3 {
+        let direction = self.centre - origin;
+        let uvw = Onb::build_from_w(direction);
+        uvw.local(random_to_sphere(self.radius, direction.length_squared()))
+    }
 }
@@ -0,0 +1,97 @@
+use std::sync::Arc;
+
+use glam::{DMat3, DMat4};
+
+use crate::{
+    aabb::Aabb,
+    ray::Ray,
+    vec3::{Point3, Vec3},
+};
+
+use super::{HitRecord, Hittable};
+
+/// Any affine transform (scale, rotation about any axis, translation, shear) of an object.
+/// The object is shared, so instancing one mesh many times doesn't copy it.
+pub struct Transform {
+    object: Arc<dyn Hittable>,
+    object_to_world: DMat4,
+    world_to_object: DMat4,
+    /// Inverse transpose of the linear part, keeps normals perpendicular under scaling
+    normal_matrix: DMat3,
+    /// Absolute determinant of the linear part of `world_to_object`
+    world_to_object_det: f64,
+}
+
+impl Transform {
+    pub fn new(object: Box<dyn Hittable>, object_to_world: DMat4) -> Self {
+        Self::instance(Arc::from(object), object_to_world)
+    }
+
+    /// Place an object that may also be placed elsewhere
+    pub fn instance(object: Arc<dyn Hittable>, object_to_world: DMat4) -> Self {
+        let world_to_object = object_to_world.inverse();
+
+        Transform {
+            object,
+            object_to_world,
+            world_to_object,
+            normal_matrix: DMat3::from_mat4(world_to_object).transpose(),
+            world_to_object_det: DMat3::from_mat4(world_to_object).determinant().abs(),
+        }
+    }
+}
+
+impl Hittable for Transform {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        // The direction isn't renormalised, so t means the same in both spaces
+        let object_ray = Ray::new(
+            self.world_to_object.transform_point3(ray.origin),
+            self.world_to_object.transform_vector3(ray.direction),
+            ray.time,
+        );
+
+        let hit_record = self.object.hit(&object_ray, t_min, t_max)?;
+
+        // Transforming normals this way keeps their dot product with the ray's direction,
+        // so front_face carries over
+        Some(HitRecord {
+            point: self.object_to_world.transform_point3(hit_record.point),
+            normal: (self.normal_matrix * hit_record.normal).normalize(),
+            dpdu: self.object_to_world.transform_vector3(hit_record.dpdu),
+            dpdv: self.object_to_world.transform_vector3(hit_record.dpdv),
+            ..hit_record
+        })
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        self.object
+            .bounding_box(time0, time1)
+            .map(|bbox| bbox.transformed(self.object_to_world))
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        let object_direction = self.world_to_object.transform_vector3(direction);
+        let density = self.object.pdf_value(
+            self.world_to_object.transform_point3(origin),
+            object_direction,
+            time,
+        );
+
+        density * solid_angle_scale(self.world_to_object_det, direction, object_direction)
+    }
+
+    fn random(&self, origin: Point3, time: f64) -> Vec3 {
+        let object_origin = self.world_to_object.transform_point3(origin);
+        self.object_to_world
+            .transform_vector3(self.object.random(object_origin, time))
+    }
+}
+
+/// How much a small solid angle around `direction` grows when mapped to
+/// `object_direction` by a linear map with absolute determinant `det`, which converts a
+/// density over object space directions into one over world space directions.
+/// Neither direction needs to be normalised.
+pub fn solid_angle_scale(det: f64, direction: Vec3, object_direction: Vec3) -> f64 {
+    let stretch = direction.length() / object_direction.length();
+    det * stretch * stretch * stretch
+}
@@ -1,4 +1,8 @@
-use crate::{aabb::Aabb, ray::Ray, vec3::Vec3};
+use crate::{
+    aabb::Aabb,
+    ray::Ray,
+    vec3::{Point3, Vec3},
+};
 
 use super::{HitRecord, Hittable};
 
@@ -12,15 +16,18 @@ impl Hittable for Translate {
         let moved_ray = Ray::new(ray.origin - self.offset, ray.direction, ray.time);
 
         if let Some(hit_record) = self.obj.hit(&moved_ray, t_min, t_max) {
-            return Some(HitRecord::new(
-                hit_record.point + self.offset,
-                hit_record.t,
-                &moved_ray,
-                hit_record.normal,
-                hit_record.material,
-                hit_record.u,
-                hit_record.v,
-            ));
+            return Some(
+                HitRecord::new(
+                    hit_record.point + self.offset,
+                    hit_record.t,
+                    &moved_ray,
+                    hit_record.normal,
+                    hit_record.material,
+                    hit_record.u,
+                    hit_record.v,
+                )
+                .with_tangents(hit_record.dpdu, hit_record.dpdv),
+            );
         }
 
         None
@@ -33,6 +40,14 @@ impl Hittable for Translate {
 
         None
     }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        self.obj.pdf_value(origin - self.offset, direction, time)
+    }
+
+    fn random(&self, origin: Point3, time: f64) -> Vec3 {
+        self.obj.random(origin - self.offset, time)
+    }
 }
 
 impl Translate {
@@ -0,0 +1,135 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    material::Material,
+    ray::Ray,
+    vec3::{Point3, Vec3},
+};
+
+use super::{HitRecord, Hittable};
+
+pub struct Triangle {
+    vertices: [Point3; 3],
+    /// Per-vertex shading normals, otherwise the flat geometric normal is used
+    normals: Option<[Vec3; 3]>,
+    /// Per-vertex texture coordinates, otherwise the barycentric coordinates are used
+    uvs: Option<[(f64, f64); 3]>,
+    material: Arc<dyn Material>,
+}
+
+impl Triangle {
+    pub fn new(vertices: [Point3; 3], material: Arc<dyn Material>) -> Self {
+        Triangle {
+            vertices,
+            normals: None,
+            uvs: None,
+            material,
+        }
+    }
+
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: [(f64, f64); 3]) -> Self {
+        self.uvs = Some(uvs);
+        self
+    }
+
+    /// Derivatives of the position along u and v, found by solving
+    /// `edge = du * dpdu + dv * dpdv` for both edges
+    fn tangents(&self, edge1: Vec3, edge2: Vec3) -> (Vec3, Vec3) {
+        // Without texture coordinates u and v are the barycentric coordinates along the edges
+        let Some([uv0, uv1, uv2]) = self.uvs else {
+            return (edge1, edge2);
+        };
+
+        let (du1, dv1) = (uv1.0 - uv0.0, uv1.1 - uv0.1);
+        let (du2, dv2) = (uv2.0 - uv0.0, uv2.1 - uv0.1);
+        let determinant = du1 * dv2 - du2 * dv1;
+        if determinant.abs() < 1e-12 {
+            return (edge1, edge2);
+        }
+
+        (
+            (dv2 * edge1 - dv1 * edge2) / determinant,
+            (du1 * edge2 - du2 * edge1) / determinant,
+        )
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        // Möller–Trumbore intersection
+        let [v0, v1, v2] = self.vertices;
+        let edge1 = v1 - v0;
+        let edge2 = v2 - v0;
+
+        let p = ray.direction.cross(edge2);
+        let determinant = edge1.dot(p);
+
+        // Ray is parallel to the triangle
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+
+        let inverse_determinant = 1.0 / determinant;
+        let s = ray.origin - v0;
+        let b1 = s.dot(p) * inverse_determinant;
+        if !(0.0..=1.0).contains(&b1) {
+            return None;
+        }
+
+        let q = s.cross(edge1);
+        let b2 = ray.direction.dot(q) * inverse_determinant;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return None;
+        }
+
+        let t = edge2.dot(q) * inverse_determinant;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let b0 = 1.0 - b1 - b2;
+        let outward_normal = match self.normals {
+            Some([n0, n1, n2]) => (b0 * n0 + b1 * n1 + b2 * n2).normalize(),
+            None => edge1.cross(edge2).normalize(),
+        };
+
+        let (u, v) = match self.uvs {
+            Some([uv0, uv1, uv2]) => (
+                b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0,
+                b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1,
+            ),
+            None => (b1, b2),
+        };
+        let (dpdu, dpdv) = self.tangents(edge1, edge2);
+
+        Some(
+            HitRecord::new(
+                ray.at(t),
+                t,
+                ray,
+                outward_normal,
+                Arc::clone(&self.material),
+                u,
+                v,
+            )
+            .with_tangents(dpdu, dpdv),
+        )
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        let [v0, v1, v2] = self.vertices;
+        // Pad so triangles lying in an axis plane still have volume
+        let padding = Vec3::new(0.0001, 0.0001, 0.0001);
+
+        Some(Aabb::new(
+            v0.min(v1).min(v2) - padding,
+            v0.max(v1).max(v2) + padding,
+        ))
+    }
+}
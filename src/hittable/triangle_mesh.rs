@@ -0,0 +1,178 @@
+use std::{error::Error, path::Path, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    material::{DiffuseLight, Lambertian, Material, Metal},
+    ray::Ray,
+    texture::{ColorSpace, ImageTexture, SolidColor, Texture},
+    vec3::{Color, Point3, Vec3},
+};
+
+use super::{
+    bvh::{Bvh, BvhBuilder},
+    hittable_list::HittableList,
+    triangle::Triangle,
+    HitRecord, Hittable,
+};
+
+/// Triangles loaded from a mesh file, grouped into a BVH
+pub struct TriangleMesh {
+    triangles: Bvh,
+}
+
+impl TriangleMesh {
+    pub fn new(triangles: HittableList, bvh: BvhBuilder) -> Self {
+        TriangleMesh {
+            triangles: Bvh::new(triangles, 0.0, 1.0, bvh),
+        }
+    }
+
+    /// Load a Wavefront OBJ file along with its MTL materials. Faces without a material
+    /// use `default_material`.
+    pub fn load_obj(
+        path: &str,
+        default_material: Arc<dyn Material>,
+        bvh: BvhBuilder,
+    ) -> Result<Self, Box<dyn Error>> {
+        let (models, materials) = tobj::load_obj(
+            path,
+            &tobj::LoadOptions {
+                single_index: true,
+                triangulate: true,
+                ignore_points: true,
+                ignore_lines: true,
+            },
+        )?;
+
+        // Textures in the MTL file are relative to the OBJ file
+        let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+        // Without its MTL file the geometry still loads, with the default material
+        let materials = materials.unwrap_or_else(|err| {
+            eprintln!("Failed to load materials for {}: {}", path, err);
+            Vec::new()
+        });
+        let materials = materials
+            .iter()
+            .map(|material| mtl_material(material, directory))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut triangles = HittableList::new();
+        for model in &models {
+            let mesh = &model.mesh;
+            let material = mesh
+                .material_id
+                .and_then(|id| materials.get(id))
+                .unwrap_or(&default_material);
+
+            let position = |index: usize| {
+                Point3::new(
+                    mesh.positions[3 * index] as f64,
+                    mesh.positions[3 * index + 1] as f64,
+                    mesh.positions[3 * index + 2] as f64,
+                )
+            };
+            let normal = |index: usize| {
+                Vec3::new(
+                    mesh.normals[3 * index] as f64,
+                    mesh.normals[3 * index + 1] as f64,
+                    mesh.normals[3 * index + 2] as f64,
+                )
+            };
+            let uv = |index: usize| {
+                (
+                    mesh.texcoords[2 * index] as f64,
+                    mesh.texcoords[2 * index + 1] as f64,
+                )
+            };
+
+            for face in mesh.indices.chunks_exact(3) {
+                let [a, b, c] = [face[0] as usize, face[1] as usize, face[2] as usize];
+                let mut triangle =
+                    Triangle::new([position(a), position(b), position(c)], material.clone());
+
+                if !mesh.normals.is_empty() {
+                    triangle = triangle.with_normals([normal(a), normal(b), normal(c)]);
+                }
+                if !mesh.texcoords.is_empty() {
+                    triangle = triangle.with_uvs([uv(a), uv(b), uv(c)]);
+                }
+
+                triangles.push(Box::new(triangle));
+            }
+        }
+
+        if triangles.is_empty() {
+            return Err(format!("No triangles in {}", path).into());
+        }
+
+        Ok(TriangleMesh::new(triangles, bvh))
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.triangles.hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        self.triangles.bounding_box(time0, time1)
+    }
+}
+
+fn to_color(color: [f32; 3]) -> Color {
+    Color::new(color[0] as f64, color[1] as f64, color[2] as f64)
+}
+
+/// Map an MTL material onto the closest of ours: emissive (Ke) becomes `DiffuseLight`,
+/// reflective (illum 3, or specular without diffuse) becomes `Metal`, anything else `Lambertian`
+fn mtl_material(
+    material: &tobj::Material,
+    directory: &Path,
+) -> Result<Arc<dyn Material>, Box<dyn Error>> {
+    if let Some(emission) = emission(material)?.filter(|emission| emission.max_element() > 0.0) {
+        return Ok(Arc::new(DiffuseLight {
+            emit: Box::new(SolidColor { color: emission }),
+        }));
+    }
+
+    let diffuse = to_color(material.diffuse.unwrap_or([0.8, 0.8, 0.8]));
+    let specular = to_color(material.specular.unwrap_or([0.0, 0.0, 0.0]));
+    let reflective = material.illumination_model == Some(3)
+        || (diffuse.max_element() == 0.0 && specular.max_element() > 0.0);
+
+    if reflective && specular.max_element() > 0.0 {
+        // Rough conversion from the Phong exponent, Ns = 0 is fully fuzzy
+        let shininess = material.shininess.unwrap_or(0.0) as f64;
+        let fuzz = (2.0 / (shininess + 2.0)).sqrt();
+
+        return Ok(Arc::new(Metal::new(specular, fuzz)));
+    }
+
+    let albedo: Box<dyn Texture> = match &material.diffuse_texture {
+        Some(texture) => Box::new(ImageTexture::open(
+            directory.join(texture),
+            ColorSpace::Srgb,
+        )?),
+        None => Box::new(SolidColor { color: diffuse }),
+    };
+
+    Ok(Arc::new(Lambertian { albedo }))
+}
+
+/// Emission isn't part of the original MTL spec, so tobj leaves it unparsed
+fn emission(material: &tobj::Material) -> Result<Option<Color>, Box<dyn Error>> {
+    let Some(ke) = material.unknown_param.get("Ke") else {
+        return Ok(None);
+    };
+
+    let values = ke
+        .split_whitespace()
+        .map(|value| value.parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match values[..] {
+        [r, g, b] => Ok(Some(Color::new(r, g, b))),
+        [v] => Ok(Some(Color::new(v, v, v))),
+        _ => Err(format!("Invalid Ke \"{}\" in {}", ke, material.name).into()),
+    }
+}
@@ -4,6 +4,7 @@ use crate::{
     aabb::Aabb,
     material::Material,
     ray::Ray,
+    utils::random_double,
     vec3::{Point3, Vec3},
 };
 
@@ -43,15 +44,21 @@ impl Hittable for XYRect {
 
         let outward_normal = Vec3::new(0.0, 0.0, 1.0);
 
-        Some(HitRecord::new(
-            point,
-            t,
-            ray,
-            outward_normal,
-            Arc::clone(&self.material),
-            u,
-            v,
-        ))
+        Some(
+            HitRecord::new(
+                point,
+                t,
+                ray,
+                outward_normal,
+                Arc::clone(&self.material),
+                u,
+                v,
+            )
+            .with_tangents(
+                Vec3::new(self.x1 - self.x0, 0.0, 0.0),
+                Vec3::new(0.0, self.y1 - self.y0, 0.0),
+            ),
+        )
     }
     fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
         Some(Aabb::new(
@@ -59,6 +66,28 @@ impl Hittable for XYRect {
             Point3::new(self.x1, self.y1, self.k + 0.0001),
         ))
     }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        let Some(hit_record) = self.hit(&Ray::new(origin, direction, time), 0.001, f64::INFINITY)
+        else {
+            return 0.0;
+        };
+
+        let area = (self.x1 - self.x0) * (self.y1 - self.y0);
+        let distance_squared = hit_record.t * hit_record.t * direction.length_squared();
+        let cosine = (direction.dot(hit_record.normal) / direction.length()).abs();
+
+        distance_squared / (cosine * area)
+    }
+
+    fn random(&self, origin: Point3, _time: f64) -> Vec3 {
+        let random_point = Point3::new(
+            random_double(self.x0, self.x1),
+            random_double(self.y0, self.y1),
+            self.k,
+        );
+        random_point - origin
+    }
 }
 
 impl XYRect {
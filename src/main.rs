@@ -1,33 +1,50 @@
 #![allow(dead_code)]
 
-#[macro_use]
-extern crate lazy_static;
-
 // TODO: Work out best way to do imports?
 // Import with crate::{...}? separate?
-use clap::Parser;
-use image::{save_buffer, ColorType};
-use renderer::render;
-use std::time::Instant;
-use window::render_window;
+use background::{Background, EnvironmentMap, SkyBackground, SolidBackground};
+use clap::{Parser, ValueEnum};
+use hittable::bvh::BvhBuilder;
+use output::{extension, is_supported, save_image};
+use renderer::{render, Accumulator, RenderSettings};
+use scene::{Scene, Shutter};
+use scene_file::load_scene;
+use sequence::Sequence;
+use std::{path::PathBuf, sync::Arc, time::Instant};
+use tonemap::{ToneMapOperator, ToneMapping};
+use vec3::Color;
+use window::{render_window, render_window_progressive, Frame};
 
-use crate::scene::{final_scene, smoke_cornell_box};
+use crate::scene::{find_scene, SCENES};
 
 mod aabb;
+mod background;
 mod camera;
+mod gltf_scene;
 mod hittable;
 mod material;
+mod microfacet;
+mod onb;
+mod output;
+mod pdf;
 mod perlin;
 mod ray;
 mod renderer;
 mod scene;
+mod scene_file;
+mod sequence;
 mod texture;
+mod tonemap;
 mod utils;
 mod vec3;
 mod window;
 #[derive(Parser, Debug)]
-#[clap(author, version, about, long_about = None)]
+#[clap(author, version, about, long_about = None, disable_help_flag = true)]
 struct Cli {
+    /// Print help
+    #[clap(long, action = clap::ArgAction::Help)]
+    help: Option<bool>,
+
     /// Width of resulting render in pixels
     #[clap(short, long)]
     width: Option<u32>,
@@ -40,40 +57,283 @@ struct Cli {
     #[clap(short, long)]
     save: bool,
 
-    /// Specify filename of render if saved
+    /// Specify filename of render if saved, .exr, .hdr and .pfm keep linear radiance
     #[clap(short, long)]
     filename: Option<String>,
 
     /// Don't render to a window
     #[clap(short = 'i', long)]
     headless: bool,
+
+    /// Tone mapping operator for the window and low dynamic range files
+    #[clap(long, value_enum, default_value_t)]
+    tone_map: ToneMapOperator,
+
+    /// Exposure adjustment in stops, applied before tone mapping
+    #[clap(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    exposure: f32,
+
+    /// Show the render in the window as samples accumulate
+    #[clap(short, long, conflicts_with = "headless")]
+    progressive: bool,
+
+    /// Built-in scene to render, see --list-scenes
+    #[clap(long, default_value = "final_scene")]
+    scene: String,
+
+    /// List the built-in scenes and exit
+    #[clap(long)]
+    list_scenes: bool,
+
+    /// Load the scene from a scene description file (TOML) or a glTF file (.gltf, .glb)
+    #[clap(long, conflicts_with = "scene")]
+    scene_file: Option<String>,
+
+    /// Samples per pixel, overrides the scene's setting
+    #[clap(long)]
+    spp: Option<u32>,
+
+    /// Maximum ray bounces, overrides the scene's setting
+    #[clap(long)]
+    max_depth: Option<u32>,
+
+    /// Background color as linear RGB, overrides the scene's setting
+    #[clap(long, num_args = 3, value_names = ["R", "G", "B"])]
+    background: Option<Vec<f64>>,
+
+    /// Use the sky gradient as background, overrides the scene's setting
+    #[clap(long, conflicts_with = "background")]
+    sky: bool,
+
+    /// Light the scene with an equirectangular environment map, overrides the scene's setting
+    #[clap(long, conflicts_with_all = ["background", "sky"])]
+    environment: Option<String>,
+
+    /// How bounding volume hierarchies are built
+    #[clap(long, value_enum, default_value_t)]
+    bvh: BvhBuilder,
+
+    /// Build and render the scene with each BVH builder in turn and compare the timings
+    #[clap(long, conflicts_with_all = ["progressive", "bvh"])]
+    benchmark: bool,
+
+    /// Render frames START to END of an animation into --output-dir, in the format of --filename
+    #[clap(long, num_args = 2, value_names = ["START", "END"], conflicts_with_all = ["progressive", "benchmark"])]
+    frames: Option<Vec<u32>>,
+
+    /// Frames per unit of scene time
+    #[clap(long, default_value_t = 24.0)]
+    fps: f64,
+
+    /// Fraction of each frame the shutter is open for, 0 turns off motion blur
+    #[clap(long, default_value_t = 0.5)]
+    shutter: f64,
+
+    /// Directory animation frames are written to, frames already there are skipped
+    #[clap(long, default_value = "frames")]
+    output_dir: String,
+
+    /// Render animation frames again even if they already exist
+    #[clap(long)]
+    overwrite: bool,
 }
 
 pub const ASPECT_RATIO: f64 = 1.0;
 pub const DEFAULT_WIDTH: u32 = 600;
-pub const MAX_DEPTH: u32 = 50;
-pub const SAMPLE_PER_PIXELS: u32 = 50;
+pub const PROGRESSIVE_PASS_SAMPLES: u32 = 1;
 
 fn main() {
     let cli = Cli::parse();
+
+    if cli.list_scenes {
+        for (name, _) in SCENES {
+            println!("{}", name);
+        }
+        return;
+    }
+
     let width = cli.width.unwrap_or(DEFAULT_WIDTH);
     let height = cli.height.unwrap_or((width as f64 / ASPECT_RATIO) as u32);
     let mut filename = cli.filename.unwrap_or_else(|| "render.png".to_string());
 
-    if !filename.ends_with(".png") {
+    if !is_supported(&filename) {
         filename.push_str(".png");
     }
 
+    // Without a shutter the scene keeps its own
+    let build_scene = |shutter: Option<Shutter>, bvh: BvhBuilder| match &cli.scene_file {
+        Some(path) => load_scene(path, width, height, shutter, bvh).unwrap_or_else(|err| {
+            eprintln!("Failed to load scene file {}: {}", path, err);
+            std::process::exit(1);
+        }),
+        None => match find_scene(&cli.scene) {
+            Some(scene) => {
+                scene(width, height, shutter.unwrap_or_default(), bvh).unwrap_or_else(|err| {
+                    eprintln!("Failed to build scene {}: {}", cli.scene, err);
+                    std::process::exit(1);
+                })
+            }
+            None => {
+                eprintln!("Unknown scene {}, see --list-scenes", cli.scene);
+                std::process::exit(1);
+            }
+        },
+    };
+
+    // Loaded up front so an animation doesn't reload the environment map every frame
+    let background: Option<Arc<dyn Background>> = if let Some(background) = &cli.background {
+        Some(Arc::new(SolidBackground {
+            color: Color::new(background[0], background[1], background[2]),
+        }))
+    } else if cli.sky {
+        Some(Arc::new(SkyBackground::default()))
+    } else if let Some(path) = &cli.environment {
+        let environment = EnvironmentMap::open(path).unwrap_or_else(|err| {
+            eprintln!("Failed to load environment map {}: {}", path, err.source);
+            std::process::exit(1);
+        });
+        Some(Arc::new(environment))
+    } else {
+        None
+    };
+
+    let scene_settings = |scene: &Scene| {
+        let mut settings = scene.settings.clone();
+        if let Some(spp) = cli.spp {
+            settings.samples_per_pixel = spp;
+        }
+        if let Some(max_depth) = cli.max_depth {
+            settings.max_depth = max_depth;
+        }
+        if let Some(background) = &background {
+            settings.background = Arc::clone(background);
+        }
+        settings
+    };
+
+    let tone_mapping = ToneMapping {
+        operator: cli.tone_map,
+        exposure: cli.exposure,
+    };
+
+    if let Some(frames) = &cli.frames {
+        let sequence = Sequence {
+            first: frames[0],
+            last: frames[1],
+            fps: cli.fps,
+            shutter: cli.shutter,
+            output_dir: PathBuf::from(&cli.output_dir),
+            extension: extension(&filename).unwrap_or_else(|| "png".to_string()),
+            overwrite: cli.overwrite,
+        };
+
+        if let Err(err) = sequence.render(
+            |shutter| build_scene(Some(shutter), cli.bvh),
+            scene_settings,
+            width,
+            height,
+            &tone_mapping,
+        ) {
+            eprintln!("Failed to render animation: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if cli.benchmark {
+        benchmark(|bvh| build_scene(None, bvh), scene_settings, width, height);
+        return;
+    }
+
     let now = Instant::now();
-    let (world, camera) = final_scene(width, height);
-    let buffer: Vec<u8> = render(width, height, &world, camera);
+    let scene = build_scene(None, cli.bvh);
+    let settings = scene_settings(&scene);
+
+    if cli.progressive {
+        let mut scene = scene;
+        let mut accumulator = Accumulator::new(width, height);
+        let mut camera_moved = false;
+        let mut now = now;
+        let save = cli.save;
+
+        let camera = scene.camera.settings;
+        render_window_progressive(width, height, camera, move |new_camera| {
+            if let Some(new_camera) = new_camera {
+                scene.camera = new_camera.build();
+                accumulator = Accumulator::new(width, height);
+                camera_moved = true;
+                now = Instant::now();
+            }
+
+            if accumulator.samples >= settings.samples_per_pixel {
+                return None;
+            }
+
+            accumulator.add_pass(&scene, &settings, PROGRESSIVE_PASS_SAMPLES);
+            let linear = accumulator.to_linear();
+
+            if accumulator.samples >= settings.samples_per_pixel {
+                println!("Rendered in {}", now.elapsed().as_secs_f64());
+                if camera_moved {
+                    println!("{:#?}", scene.camera.settings);
+                }
+                // Reported rather than unwrapped, which would only take down the render
+                // thread and leave the window open
+                if save {
+                    if let Err(err) = save_image(&filename, &linear, width, height, &tone_mapping) {
+                        eprintln!("Failed to save {}: {}", filename, err);
+                    }
+                }
+            }
+
+            Some(Frame {
+                buffer: tone_mapping.encode_rgba8(&linear),
+                samples: accumulator.samples,
+            })
+        })
+        .unwrap();
+        return;
+    }
+
+    let linear: Vec<f32> = render(width, height, &scene, &settings);
     println!("Rendered in {}", now.elapsed().as_secs_f64());
 
     if cli.save {
-        save_buffer(filename, &buffer, width, height, ColorType::Rgba8).unwrap();
+        if let Err(err) = save_image(&filename, &linear, width, height, &tone_mapping) {
+            eprintln!("Failed to save {}: {}", filename, err);
+            std::process::exit(1);
+        }
     }
 
     if !cli.headless {
-        render_window(width, height, &buffer).unwrap();
+        render_window(width, height, &tone_mapping.encode_rgba8(&linear)).unwrap();
+    }
+}
+
+/// Time building and rendering the scene with every BVH builder
+fn benchmark(
+    build_scene: impl Fn(BvhBuilder) -> Scene,
+    scene_settings: impl Fn(&Scene) -> RenderSettings,
+    width: u32,
+    height: u32,
+) {
+    println!("{:<8} {:>10} {:>10}", "bvh", "build (s)", "render (s)");
+
+    for builder in BvhBuilder::value_variants() {
+        let now = Instant::now();
+        let scene = build_scene(*builder);
+        let build_time = now.elapsed().as_secs_f64();
+
+        let settings = scene_settings(&scene);
+        let now = Instant::now();
+        render(width, height, &scene, &settings);
+        let render_time = now.elapsed().as_secs_f64();
+
+        println!(
+            "{:<8} {:>10.3} {:>10.3}",
+            format!("{:?}", builder),
+            build_time,
+            render_time
+        );
     }
 }
@@ -1,13 +1,48 @@
+use std::{f64::consts::PI, sync::Arc};
+
 use crate::{
     hittable::HitRecord,
+    microfacet::{alpha, fresnel_schlick, ggx_specular},
+    onb::Onb,
+    pdf::{ClearcoatPdf, CosinePdf, GgxPdf, Pdf, SpherePdf},
     ray::Ray,
     texture::{SolidColor, Texture},
-    utils::{random_double, random_in_unit_sphere, random_unit_vector},
+    utils::{random_double, random_in_unit_sphere},
     vec3::{Color, Point3, Vec3, VecOps},
 };
 
+pub enum Scatter {
+    /// Perfectly specular bounce, followed without importance sampling
+    Specular(Ray),
+    /// Diffuse bounce, sampled from the given distribution
+    Pdf(Box<dyn Pdf>),
+}
+
+pub struct ScatterRecord {
+    pub attenuation: Color,
+    pub scatter: Scatter,
+}
+
 pub trait Material: Send + Sync {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)>;
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord>;
+
+    /// Density with which the material itself would scatter `r_in` into `scattered`
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        0.0
+    }
+
+    /// Light scattered from `scattered` back along `r_in`, per unit of incoming light
+    /// (BRDF times cosine). Materials whose color changes with direction override this,
+    /// the rest scale `attenuation` by `scattering_pdf`.
+    fn scattering(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        scattered: &Ray,
+        attenuation: Color,
+    ) -> Color {
+        attenuation * self.scattering_pdf(r_in, rec, scattered)
+    }
 
     fn emitted(&self, _u: f64, _v: f64, _p: Point3) -> Color {
         Color::new(0.0, 0.0, 0.0)
@@ -19,53 +54,324 @@ pub struct Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
-        let mut scatter_direction = rec.normal + random_unit_vector();
-
-        // Catch degenerate scatter direction
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal;
-        }
-
-        let attenuation = self.albedo.value(rec.u, rec.v, rec.point);
-        let scattered = Ray::new(rec.point, scatter_direction, r_in.time);
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        Some(ScatterRecord {
+            attenuation: self.albedo.value(rec.u, rec.v, rec.point),
+            scatter: Scatter::Pdf(Box::new(CosinePdf::new(rec.normal))),
+        })
+    }
 
-        Some((attenuation, scattered))
+    fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let cosine = rec.normal.dot(scattered.direction.normalize());
+        (cosine / PI).max(0.0)
     }
 }
 
 pub struct Metal {
-    pub albedo: Color,
-    pub fuzz: f64, // TODO: Make a new function, fuzz cannot be > 1
+    pub albedo: Box<dyn Texture>,
+    /// Clamped to at most 1 where it's looked up
+    pub fuzz: Box<dyn Texture>,
+}
+
+impl Metal {
+    pub fn new(albedo: Color, fuzz: f64) -> Self {
+        Self::textured(
+            Box::new(SolidColor { color: albedo }),
+            Box::new(SolidColor::splat(fuzz)),
+        )
+    }
+
+    pub fn textured(albedo: Box<dyn Texture>, fuzz: Box<dyn Texture>) -> Self {
+        Metal { albedo, fuzz }
+    }
 }
 
 impl Material for Metal {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let fuzz = self.fuzz.scalar(rec.u, rec.v, rec.point).clamp(0.0, 1.0);
         let reflected = Vec3::reflect(r_in.direction.normalize(), rec.normal);
         let scattered = Ray::new(
             rec.point,
-            reflected + self.fuzz * random_in_unit_sphere(),
+            reflected + fuzz * random_in_unit_sphere(),
             r_in.time,
         );
 
-        let attenuation = self.albedo;
         if scattered.direction.dot(rec.normal) > 0f64 {
-            return Some((attenuation, scattered));
+            return Some(ScatterRecord {
+                attenuation: self.albedo.value(rec.u, rec.v, rec.point),
+                scatter: Scatter::Specular(scattered),
+            });
         }
 
         None
     }
 }
 
+/// GGX microfacet surface in the metallic-roughness parameterisation of glTF and real-time
+/// engines. Metals tint their reflections with `base_color`, everything else reflects
+/// a little white light over a diffuse `base_color`.
+pub struct Microfacet {
+    pub base_color: Box<dyn Texture>,
+    pub metallic: Box<dyn Texture>,
+    pub roughness: Box<dyn Texture>,
+}
+
+impl Microfacet {
+    pub fn new(base_color: Color, metallic: f64, roughness: f64) -> Self {
+        Self::textured(
+            Box::new(SolidColor { color: base_color }),
+            Box::new(SolidColor::splat(metallic)),
+            Box::new(SolidColor::splat(roughness)),
+        )
+    }
+
+    pub fn textured(
+        base_color: Box<dyn Texture>,
+        metallic: Box<dyn Texture>,
+        roughness: Box<dyn Texture>,
+    ) -> Self {
+        Microfacet {
+            base_color,
+            metallic,
+            roughness,
+        }
+    }
+
+    /// Metallic and roughness at a hit
+    fn parameters(&self, rec: &HitRecord) -> (f64, f64) {
+        (
+            self.metallic
+                .scalar(rec.u, rec.v, rec.point)
+                .clamp(0.0, 1.0),
+            self.roughness
+                .scalar(rec.u, rec.v, rec.point)
+                .clamp(0.0, 1.0),
+        )
+    }
+}
+
+impl Material for Microfacet {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let base_color = self.base_color.value(rec.u, rec.v, rec.point);
+        let (metallic, roughness) = self.parameters(rec);
+        let view = -r_in.direction.normalize();
+
+        // Sample the specular lobe about as often as it reflects light compared to the diffuse
+        let fresnel = fresnel_schlick(f0(base_color, metallic), rec.normal.dot(view));
+        let specular = fresnel.element_sum() / 3.0;
+        let diffuse = (1.0 - metallic) * (1.0 - specular) * base_color.element_sum() / 3.0;
+        let specular_weight = (specular / (specular + diffuse).max(1e-6)).clamp(0.1, 1.0);
+
+        Some(ScatterRecord {
+            attenuation: base_color,
+            scatter: Scatter::Pdf(Box::new(GgxPdf::new(
+                rec.normal,
+                view,
+                alpha(roughness),
+                specular_weight,
+            ))),
+        })
+    }
+
+    fn scattering(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray, base_color: Color) -> Color {
+        let view = -r_in.direction.normalize();
+        let light = scattered.direction.normalize();
+        let n_dot_l = rec.normal.dot(light);
+        if n_dot_l <= 0.0 {
+            return Color::ZERO;
+        }
+
+        let (metallic, roughness) = self.parameters(rec);
+        let f0 = f0(base_color, metallic);
+        let specular = ggx_specular(rec.normal, view, light, f0, alpha(roughness));
+
+        // Light not reflected at the surface is what enters it to scatter diffusely
+        let half = (view + light).normalize();
+        let transmitted = Color::ONE - fresnel_schlick(f0, view.dot(half));
+        let diffuse = transmitted * (1.0 - metallic) * base_color * n_dot_l / PI;
+
+        specular + diffuse
+    }
+}
+
+/// Reflectance at normal incidence, 4% for dielectrics and the base color for metals
+fn f0(base_color: Color, metallic: f64) -> Color {
+    Color::splat(0.04).lerp(base_color, metallic)
+}
+
+/// One material for most real surfaces, after Disney's principled BRDF. A metallic-roughness
+/// GGX base with a diffuse lobe and sheen under it, an optional clearcoat over it, and smooth
+/// transmission through it. Every parameter can be textured, scalars read the mean of the
+/// texture's channels.
+pub struct Principled {
+    pub base_color: Box<dyn Texture>,
+    pub metallic: Box<dyn Texture>,
+    pub roughness: Box<dyn Texture>,
+    /// Dielectric reflectance, 0.5 is the 4% at normal incidence of most materials
+    pub specular: Box<dyn Texture>,
+    pub clearcoat: Box<dyn Texture>,
+    pub clearcoat_roughness: Box<dyn Texture>,
+    /// Soft white rim at grazing angles, for cloth
+    pub sheen: Box<dyn Texture>,
+    /// Fraction of light passing through like glass, tinted by `base_color`
+    pub transmission: Box<dyn Texture>,
+    /// Index of refraction for transmission
+    pub ir: Box<dyn Texture>,
+}
+
+/// Principled parameters looked up at a hit
+struct PrincipledSample {
+    base_color: Color,
+    metallic: f64,
+    roughness: f64,
+    specular: f64,
+    clearcoat: f64,
+    clearcoat_roughness: f64,
+    sheen: f64,
+    transmission: f64,
+}
+
+impl Principled {
+    /// Rough white plastic, change the other parameters with struct update syntax
+    pub fn new(base_color: Color) -> Self {
+        Principled {
+            base_color: Box::new(SolidColor { color: base_color }),
+            metallic: Box::new(SolidColor::splat(0.0)),
+            roughness: Box::new(SolidColor::splat(0.5)),
+            specular: Box::new(SolidColor::splat(0.5)),
+            clearcoat: Box::new(SolidColor::splat(0.0)),
+            clearcoat_roughness: Box::new(SolidColor::splat(0.1)),
+            sheen: Box::new(SolidColor::splat(0.0)),
+            transmission: Box::new(SolidColor::splat(0.0)),
+            ir: Box::new(SolidColor::splat(1.5)),
+        }
+    }
+
+    fn sample(&self, rec: &HitRecord) -> PrincipledSample {
+        let scalar =
+            |texture: &dyn Texture| texture.scalar(rec.u, rec.v, rec.point).clamp(0.0, 1.0);
+
+        PrincipledSample {
+            base_color: self.base_color.value(rec.u, rec.v, rec.point),
+            metallic: scalar(self.metallic.as_ref()),
+            roughness: scalar(self.roughness.as_ref()),
+            specular: scalar(self.specular.as_ref()),
+            clearcoat: scalar(self.clearcoat.as_ref()),
+            clearcoat_roughness: scalar(self.clearcoat_roughness.as_ref()),
+            sheen: scalar(self.sheen.as_ref()),
+            transmission: scalar(self.transmission.as_ref()),
+        }
+    }
+}
+
+impl PrincipledSample {
+    fn f0(&self) -> Color {
+        Color::splat(0.08 * self.specular).lerp(self.base_color, self.metallic)
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let sample = self.sample(rec);
+
+        // Pick glass or the opaque layers by how much light each accounts for, so neither
+        // needs weighting
+        if random_double(0.0, 1.0) < sample.transmission * (1.0 - sample.metallic) {
+            return Some(ScatterRecord {
+                attenuation: sample.base_color,
+                scatter: Scatter::Specular(Dielectric::scattered(r_in, rec, self.ir.as_ref())),
+            });
+        }
+
+        let view = -r_in.direction.normalize();
+        let n_dot_v = rec.normal.dot(view);
+
+        // Sample each lobe about as often as it reflects light, as for `Microfacet`
+        let clearcoat = sample.clearcoat * fresnel_schlick(Color::splat(0.04), n_dot_v).x;
+        let specular = fresnel_schlick(sample.f0(), n_dot_v).element_sum() / 3.0;
+        let diffuse = (1.0 - sample.metallic)
+            * (1.0 - specular)
+            * (sample.base_color.element_sum() / 3.0 + sample.sheen);
+        let specular_weight = (specular / (specular + diffuse).max(1e-6)).clamp(0.1, 1.0);
+
+        let base = GgxPdf::new(rec.normal, view, alpha(sample.roughness), specular_weight);
+        let pdf: Box<dyn Pdf> = match sample.clearcoat > 0.0 {
+            true => Box::new(ClearcoatPdf::new(
+                base,
+                alpha(sample.clearcoat_roughness),
+                (clearcoat / (clearcoat + specular + diffuse).max(1e-6)).clamp(0.1, 0.5),
+            )),
+            false => Box::new(base),
+        };
+
+        Some(ScatterRecord {
+            attenuation: sample.base_color,
+            scatter: Scatter::Pdf(pdf),
+        })
+    }
+
+    fn scattering(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray, _: Color) -> Color {
+        let sample = self.sample(rec);
+        let view = -r_in.direction.normalize();
+        let light = scattered.direction.normalize();
+        let n_dot_l = rec.normal.dot(light);
+        if n_dot_l <= 0.0 {
+            return Color::ZERO;
+        }
+
+        let half = (view + light).normalize();
+        let f0 = sample.f0();
+        let specular = ggx_specular(rec.normal, view, light, f0, alpha(sample.roughness));
+
+        let transmitted = Color::ONE - fresnel_schlick(f0, view.dot(half));
+        let diffuse = transmitted * sample.base_color / PI;
+        let sheen = Color::splat(sample.sheen * (1.0 - light.dot(half)).clamp(0.0, 1.0).powi(5));
+        let base = specular + (1.0 - sample.metallic) * (diffuse + sheen) * n_dot_l;
+
+        if sample.clearcoat <= 0.0 {
+            return base;
+        }
+
+        // The coat reflects a little white light and lets the rest through to the base
+        let coat_f0 = Color::splat(0.04);
+        let alpha = alpha(sample.clearcoat_roughness);
+        let coat = sample.clearcoat * ggx_specular(rec.normal, view, light, coat_f0, alpha);
+        let coat_fresnel = sample.clearcoat * fresnel_schlick(coat_f0, view.dot(half)).x;
+
+        coat + (1.0 - coat_fresnel) * base
+    }
+}
+
 pub struct Dielectric {
-    pub ir: f64,
+    pub ir: Box<dyn Texture>,
+}
+
+impl Dielectric {
+    pub fn new(ir: f64) -> Self {
+        Self::textured(Box::new(SolidColor::splat(ir)))
+    }
+
+    pub fn textured(ir: Box<dyn Texture>) -> Self {
+        Dielectric { ir }
+    }
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        Some(ScatterRecord {
+            attenuation: Color::new(1.0, 1.0, 1.0),
+            scatter: Scatter::Specular(Dielectric::scattered(r_in, rec, self.ir.as_ref())),
+        })
+    }
+}
+
+impl Dielectric {
+    /// Reflect or refract at random, weighted by the Fresnel reflectance
+    fn scattered(r_in: &Ray, rec: &HitRecord, ir: &dyn Texture) -> Ray {
+        let ir = ir.scalar(rec.u, rec.v, rec.point);
         let refraction_ratio = match rec.front_face {
-            true => 1.0 / self.ir,
-            false => self.ir,
+            true => 1.0 / ir,
+            false => ir,
         };
 
         let unit_direction = r_in.direction.normalize();
@@ -81,12 +387,9 @@ impl Material for Dielectric {
             false => refract(unit_direction, rec.normal, refraction_ratio),
         };
 
-        let scattered = Ray::new(rec.point, direction, r_in.time);
-        Some((Color::new(1.0, 1.0, 1.0), scattered))
+        Ray::new(rec.point, direction, r_in.time)
     }
-}
 
-impl Dielectric {
     fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
         // Use Schlick's approximation for reflectance.
         let mut r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
@@ -103,11 +406,11 @@ fn refract(uv: Vec3, n: Vec3, etai_over_etat: f64) -> Vec3 {
 }
 
 pub struct DiffuseLight {
-    emit: Box<dyn Texture>,
+    pub emit: Box<dyn Texture>,
 }
 
 impl Material for DiffuseLight {
-    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<ScatterRecord> {
         None
     }
 
@@ -118,28 +421,182 @@ impl Material for DiffuseLight {
 
 impl DiffuseLight {
     pub fn new(emit: Color) -> Self {
-        DiffuseLight {
-            emit: Box::new(SolidColor { color: emit }),
-        }
+        Self::textured(Box::new(SolidColor { color: emit }))
+    }
+
+    pub fn textured(emit: Box<dyn Texture>) -> Self {
+        DiffuseLight { emit }
     }
 }
 
 pub struct Isotropic {
-    albedo: Box<dyn Texture>,
+    pub albedo: Box<dyn Texture>,
 }
 
 impl Material for Isotropic {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
-        let scattered = Ray::new(rec.point, random_in_unit_sphere(), r_in.time);
-        let attenuation = self.albedo.value(rec.u, rec.v, rec.point);
-        Some((attenuation, scattered))
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        Some(ScatterRecord {
+            attenuation: self.albedo.value(rec.u, rec.v, rec.point),
+            scatter: Scatter::Pdf(Box::new(SpherePdf)),
+        })
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        1.0 / (4.0 * PI)
     }
 }
 
 impl Isotropic {
     pub fn new(color: Color) -> Self {
-        Isotropic {
-            albedo: Box::new(SolidColor { color }),
+        Self::textured(Box::new(SolidColor { color }))
+    }
+
+    pub fn textured(albedo: Box<dyn Texture>) -> Self {
+        Isotropic { albedo }
+    }
+}
+
+/// Step in u and v for finite differences of bump heights
+const BUMP_STEP: f64 = 0.0005;
+
+/// Another material with its shading normal tilted by the slope of a height texture, any
+/// scalar texture such as `NoiseTexture` will do
+pub struct BumpMap {
+    pub material: Arc<dyn Material>,
+    pub height: Box<dyn Texture>,
+    /// Height in world units of a texture value of 1
+    pub scale: f64,
+}
+
+impl BumpMap {
+    pub fn new(material: Arc<dyn Material>, height: Box<dyn Texture>, scale: f64) -> Self {
+        BumpMap {
+            material,
+            height,
+            scale,
+        }
+    }
+
+    fn shade(&self, rec: &HitRecord) -> HitRecord {
+        let height = |du: f64, dv: f64| {
+            let p = rec.point + du * rec.dpdu + dv * rec.dpdv;
+            self.scale * self.height.scalar(rec.u + du, rec.v + dv, p)
+        };
+        let base = height(0.0, 0.0);
+        let dhdu = (height(BUMP_STEP, 0.0) - base) / BUMP_STEP;
+        let dhdv = (height(0.0, BUMP_STEP) - base) / BUMP_STEP;
+
+        // Displacing the surface along the normal changes its tangents and so its normal
+        let normal = rec.outward_normal();
+        let dpdu = rec.dpdu + dhdu * normal;
+        let dpdv = rec.dpdv + dhdv * normal;
+        let bumped = match dpdu.cross(dpdv).try_normalize() {
+            Some(bumped) if bumped.dot(normal) < 0.0 => -bumped,
+            Some(bumped) => bumped,
+            None => normal,
+        };
+
+        with_shading_normal(rec, bumped)
+    }
+}
+
+impl Material for BumpMap {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        self.material.scatter(r_in, &self.shade(rec))
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.material
+            .scattering_pdf(r_in, &self.shade(rec), scattered)
+    }
+
+    fn scattering(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        scattered: &Ray,
+        attenuation: Color,
+    ) -> Color {
+        self.material
+            .scattering(r_in, &self.shade(rec), scattered, attenuation)
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: Point3) -> Color {
+        self.material.emitted(u, v, p)
+    }
+}
+
+/// Another material with its shading normal read from a tangent-space normal map, usually
+/// an `ImageTexture`. Colors encode normals with x along u, y along v and z out of the surface.
+pub struct NormalMap {
+    pub material: Arc<dyn Material>,
+    pub normals: Box<dyn Texture>,
+    /// Scales how far the normals tilt, as glTF's normal texture scale
+    pub strength: f64,
+}
+
+impl NormalMap {
+    pub fn new(material: Arc<dyn Material>, normals: Box<dyn Texture>, strength: f64) -> Self {
+        NormalMap {
+            material,
+            normals,
+            strength,
         }
     }
+
+    fn shade(&self, rec: &HitRecord) -> HitRecord {
+        // Orthonormal tangent frame following u and v as closely as it can
+        let normal = rec.outward_normal();
+        let tangent = (rec.dpdu - normal.dot(rec.dpdu) * normal)
+            .try_normalize()
+            .unwrap_or_else(|| Onb::build_from_w(normal).u);
+        let bitangent =
+            (rec.dpdv - normal.dot(rec.dpdv) * normal - tangent.dot(rec.dpdv) * tangent)
+                .try_normalize()
+                .unwrap_or_else(|| normal.cross(tangent));
+
+        let encoded = 2.0 * self.normals.value(rec.u, rec.v, rec.point) - Color::ONE;
+        let mapped =
+            self.strength * (encoded.x * tangent + encoded.y * bitangent) + encoded.z * normal;
+
+        with_shading_normal(rec, mapped.try_normalize().unwrap_or(normal))
+    }
+}
+
+impl Material for NormalMap {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        self.material.scatter(r_in, &self.shade(rec))
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.material
+            .scattering_pdf(r_in, &self.shade(rec), scattered)
+    }
+
+    fn scattering(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        scattered: &Ray,
+        attenuation: Color,
+    ) -> Color {
+        self.material
+            .scattering(r_in, &self.shade(rec), scattered, attenuation)
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: Point3) -> Color {
+        self.material.emitted(u, v, p)
+    }
+}
+
+/// `rec` shaded with an outward facing `normal`, turned to the side that was hit
+fn with_shading_normal(rec: &HitRecord, normal: Vec3) -> HitRecord {
+    HitRecord {
+        normal: match rec.front_face {
+            true => normal,
+            false => -normal,
+        },
+        material: Arc::clone(&rec.material),
+        ..*rec
+    }
 }
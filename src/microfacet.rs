@@ -0,0 +1,72 @@
+use std::f64::consts::PI;
+
+use crate::{
+    utils::random_double,
+    vec3::{Color, Vec3},
+};
+
+/// Roughness is squared into GGX's alpha, as in glTF, and kept off zero so highlights
+/// stay finite
+pub fn alpha(roughness: f64) -> f64 {
+    (roughness * roughness).max(1e-3)
+}
+
+/// GGX (Trowbridge-Reitz) normal distribution
+pub fn ggx_d(n_dot_h: f64, alpha: f64) -> f64 {
+    let alpha2 = alpha * alpha;
+    let denominator = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+    alpha2 / (PI * denominator * denominator)
+}
+
+/// Smith shadowing-masking for one direction
+pub fn smith_g1(n_dot_v: f64, alpha: f64) -> f64 {
+    let alpha2 = alpha * alpha;
+    2.0 * n_dot_v / (n_dot_v + (alpha2 + (1.0 - alpha2) * n_dot_v * n_dot_v).sqrt())
+}
+
+/// Schlick's approximation with a colored reflectance at normal incidence
+pub fn fresnel_schlick(f0: Color, cosine: f64) -> Color {
+    f0 + (Color::ONE - f0) * (1.0 - cosine).clamp(0.0, 1.0).powi(5)
+}
+
+/// Half vector about +z distributed by `ggx_d(n_dot_h) * n_dot_h`
+pub fn sample_ggx_half_vector(alpha: f64) -> Vec3 {
+    let r1 = random_double(0.0, 1.0);
+    let r2 = random_double(0.0, 1.0);
+
+    let cos_theta = ((1.0 - r1) / (1.0 + (alpha * alpha - 1.0) * r1)).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * r2;
+
+    Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta)
+}
+
+/// Density of reflecting `view` into `light` about a half vector sampled with
+/// `sample_ggx_half_vector`, all unit vectors
+pub fn ggx_reflection_pdf(normal: Vec3, view: Vec3, light: Vec3, alpha: f64) -> f64 {
+    let half = (view + light).normalize();
+    let n_dot_h = normal.dot(half);
+    let v_dot_h = view.dot(half);
+    if n_dot_h <= 0.0 || v_dot_h <= 0.0 {
+        return 0.0;
+    }
+
+    ggx_d(n_dot_h, alpha) * n_dot_h / (4.0 * v_dot_h)
+}
+
+/// Cook-Torrance specular BRDF times the cosine term
+pub fn ggx_specular(normal: Vec3, view: Vec3, light: Vec3, f0: Color, alpha: f64) -> Color {
+    let n_dot_v = normal.dot(view);
+    let n_dot_l = normal.dot(light);
+    if n_dot_v <= 0.0 || n_dot_l <= 0.0 {
+        return Color::ZERO;
+    }
+
+    let half = (view + light).normalize();
+    let d = ggx_d(normal.dot(half).max(0.0), alpha);
+    let g = smith_g1(n_dot_v, alpha) * smith_g1(n_dot_l, alpha);
+    let f = fresnel_schlick(f0, view.dot(half));
+
+    // D G F / (4 n.v n.l), times n.l
+    f * (d * g / (4.0 * n_dot_v))
+}
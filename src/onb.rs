@@ -0,0 +1,27 @@
+use crate::vec3::Vec3;
+
+/// Orthonormal basis, used to transform directions generated around the z-axis
+/// into directions around an arbitrary normal
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    pub fn build_from_w(n: Vec3) -> Self {
+        let w = n.normalize();
+        let a = match w.x.abs() > 0.9 {
+            true => Vec3::new(0.0, 1.0, 0.0),
+            false => Vec3::new(1.0, 0.0, 0.0),
+        };
+        let v = w.cross(a).normalize();
+        let u = w.cross(v);
+
+        Onb { u, v, w }
+    }
+
+    pub fn local(&self, a: Vec3) -> Vec3 {
+        a.x * self.u + a.y * self.v + a.z * self.w
+    }
+}
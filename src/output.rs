@@ -0,0 +1,75 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use image::{DynamicImage, ImageFormat, Rgb32FImage, RgbaImage};
+
+use crate::tonemap::ToneMapping;
+
+/// Extensions that `save_image` writes as unclamped linear radiance
+pub const HDR_EXTENSIONS: [&str; 3] = ["exr", "hdr", "pfm"];
+
+/// Whether `save_image` knows the format for `filename`'s extension
+pub fn is_supported(filename: &str) -> bool {
+    ImageFormat::from_path(filename).is_ok() || extension(filename) == Some("pfm".to_string())
+}
+
+/// Save a linear RGB framebuffer, rows top to bottom, picking the format from the extension.
+/// OpenEXR, Radiance HDR and PFM keep the linear values, anything else is tone mapped.
+pub fn save_image(
+    filename: &str,
+    linear: &[f32],
+    width: u32,
+    height: u32,
+    tone_mapping: &ToneMapping,
+) -> Result<(), Box<dyn Error>> {
+    match extension(filename).as_deref() {
+        Some("exr") => save_float(filename, linear, width, height, ImageFormat::OpenExr),
+        Some("hdr") => save_float(filename, linear, width, height, ImageFormat::Hdr),
+        Some("pfm") => save_pfm(filename, linear, width, height),
+        _ => {
+            let image = RgbaImage::from_raw(width, height, tone_mapping.encode_rgba8(linear))
+                .ok_or("Framebuffer does not match image dimensions")?;
+            // Drop alpha so formats without it (e.g. JPEG) can be written too
+            DynamicImage::ImageRgba8(image).to_rgb8().save(filename)?;
+            Ok(())
+        }
+    }
+}
+
+pub fn extension(filename: &str) -> Option<String> {
+    Path::new(filename)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+}
+
+fn save_float(
+    filename: &str,
+    linear: &[f32],
+    width: u32,
+    height: u32,
+    format: ImageFormat,
+) -> Result<(), Box<dyn Error>> {
+    let image = Rgb32FImage::from_raw(width, height, linear.to_vec())
+        .ok_or("Framebuffer does not match image dimensions")?;
+    image.save_with_format(filename, format)?;
+    Ok(())
+}
+
+/// Portable float map, little endian with rows stored bottom to top
+fn save_pfm(filename: &str, linear: &[f32], width: u32, height: u32) -> Result<(), Box<dyn Error>> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    write!(writer, "PF\n{} {}\n-1.0\n", width, height)?;
+
+    for row in linear.chunks_exact(3 * width as usize).rev() {
+        for value in row {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
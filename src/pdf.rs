@@ -0,0 +1,185 @@
+use std::f64::consts::PI;
+
+use crate::{
+    hittable::Hittable,
+    microfacet::{ggx_reflection_pdf, sample_ggx_half_vector},
+    onb::Onb,
+    utils::{random_cosine_direction, random_double, random_unit_vector},
+    vec3::{Point3, Vec3},
+};
+
+/// Probability density function over directions, used for importance sampling
+pub trait Pdf {
+    fn value(&self, direction: Vec3) -> f64;
+    fn generate(&self) -> Vec3;
+}
+
+/// Cosine weighted distribution about a surface normal
+pub struct CosinePdf {
+    uvw: Onb,
+}
+
+impl CosinePdf {
+    pub fn new(normal: Vec3) -> Self {
+        CosinePdf {
+            uvw: Onb::build_from_w(normal),
+        }
+    }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: Vec3) -> f64 {
+        let cosine = direction.normalize().dot(self.uvw.w);
+        (cosine / PI).max(0.0)
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.uvw.local(random_cosine_direction())
+    }
+}
+
+/// Uniform distribution over the whole sphere of directions
+pub struct SpherePdf;
+
+impl Pdf for SpherePdf {
+    fn value(&self, _direction: Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn generate(&self) -> Vec3 {
+        random_unit_vector()
+    }
+}
+
+/// GGX reflection lobe about the mirror direction of `view`, mixed with a cosine lobe for
+/// the diffuse part of the surface
+pub struct GgxPdf {
+    uvw: Onb,
+    view: Vec3,
+    alpha: f64,
+    /// Chance of sampling the specular lobe rather than the cosine one
+    specular_weight: f64,
+}
+
+impl GgxPdf {
+    pub fn new(normal: Vec3, view: Vec3, alpha: f64, specular_weight: f64) -> Self {
+        GgxPdf {
+            uvw: Onb::build_from_w(normal),
+            view: view.normalize(),
+            alpha,
+            specular_weight,
+        }
+    }
+}
+
+impl Pdf for GgxPdf {
+    fn value(&self, direction: Vec3) -> f64 {
+        let direction = direction.normalize();
+        let specular = ggx_reflection_pdf(self.uvw.w, self.view, direction, self.alpha);
+        let diffuse = (direction.dot(self.uvw.w) / PI).max(0.0);
+
+        self.specular_weight * specular + (1.0 - self.specular_weight) * diffuse
+    }
+
+    fn generate(&self) -> Vec3 {
+        if random_double(0.0, 1.0) < self.specular_weight {
+            let half = self.uvw.local(sample_ggx_half_vector(self.alpha));
+            2.0 * self.view.dot(half) * half - self.view
+        } else {
+            self.uvw.local(random_cosine_direction())
+        }
+    }
+}
+
+/// `GgxPdf` with a second, usually sharper, GGX lobe for a clearcoat layer
+pub struct ClearcoatPdf {
+    base: GgxPdf,
+    alpha: f64,
+    /// Chance of sampling the clearcoat lobe rather than the base
+    clearcoat_weight: f64,
+}
+
+impl ClearcoatPdf {
+    pub fn new(base: GgxPdf, alpha: f64, clearcoat_weight: f64) -> Self {
+        ClearcoatPdf {
+            base,
+            alpha,
+            clearcoat_weight,
+        }
+    }
+}
+
+impl Pdf for ClearcoatPdf {
+    fn value(&self, direction: Vec3) -> f64 {
+        let clearcoat = ggx_reflection_pdf(
+            self.base.uvw.w,
+            self.base.view,
+            direction.normalize(),
+            self.alpha,
+        );
+
+        self.clearcoat_weight * clearcoat
+            + (1.0 - self.clearcoat_weight) * self.base.value(direction)
+    }
+
+    fn generate(&self) -> Vec3 {
+        if random_double(0.0, 1.0) < self.clearcoat_weight {
+            let half = self.base.uvw.local(sample_ggx_half_vector(self.alpha));
+            2.0 * self.base.view.dot(half) * half - self.base.view
+        } else {
+            self.base.generate()
+        }
+    }
+}
+
+/// Distribution of directions from `origin` towards a hittable
+pub struct HittablePdf<'a> {
+    origin: Point3,
+    time: f64,
+    hittable: &'a dyn Hittable,
+}
+
+impl<'a> HittablePdf<'a> {
+    pub fn new(hittable: &'a dyn Hittable, origin: Point3, time: f64) -> Self {
+        HittablePdf {
+            origin,
+            time,
+            hittable,
+        }
+    }
+}
+
+impl Pdf for HittablePdf<'_> {
+    fn value(&self, direction: Vec3) -> f64 {
+        self.hittable.pdf_value(self.origin, direction, self.time)
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.hittable.random(self.origin, self.time)
+    }
+}
+
+/// Equal weighted mix of two distributions
+pub struct MixturePdf<'a> {
+    p: [&'a dyn Pdf; 2],
+}
+
+impl<'a> MixturePdf<'a> {
+    pub fn new(p0: &'a dyn Pdf, p1: &'a dyn Pdf) -> Self {
+        MixturePdf { p: [p0, p1] }
+    }
+}
+
+impl Pdf for MixturePdf<'_> {
+    fn value(&self, direction: Vec3) -> f64 {
+        0.5 * self.p[0].value(direction) + 0.5 * self.p[1].value(direction)
+    }
+
+    fn generate(&self) -> Vec3 {
+        if random_double(0.0, 1.0) < 0.5 {
+            self.p[0].generate()
+        } else {
+            self.p[1].generate()
+        }
+    }
+}
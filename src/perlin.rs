@@ -1,15 +1,14 @@
 use itertools::Itertools;
-use rand::{prelude::SliceRandom, thread_rng};
+use rand::{prelude::SliceRandom, rngs::StdRng, thread_rng, Rng, SeedableRng};
 
-use crate::{
-    utils::random_unit_vector,
-    vec3::{Point3, Vec3},
-};
+use crate::vec3::{Point3, Vec3};
 
 const POINT_COUNT: usize = 256;
 
 pub struct Perlin {
     ran_vec: [Vec3; POINT_COUNT],
+    /// Worley feature points, one per lattice cell, within the unit cube
+    ran_point: [Point3; POINT_COUNT],
     perm_x: [i64; POINT_COUNT],
     perm_y: [i64; POINT_COUNT],
     perm_z: [i64; POINT_COUNT],
@@ -17,19 +16,49 @@ pub struct Perlin {
 
 impl Perlin {
     pub fn new() -> Self {
+        Self::from_rng(&mut thread_rng())
+    }
+
+    /// The same seed always gives the same noise
+    pub fn seeded(seed: u64) -> Self {
+        Self::from_rng(&mut StdRng::seed_from_u64(seed))
+    }
+
+    fn from_rng(rng: &mut impl Rng) -> Self {
         let mut ran_vec: [Vec3; POINT_COUNT] = [Vec3::default(); POINT_COUNT];
         for vec in ran_vec.iter_mut() {
-            *vec = random_unit_vector();
+            *vec = loop {
+                let p = Vec3::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                );
+                if p.length_squared() < 1.0 && p.length_squared() > 1e-6 {
+                    break p.normalize();
+                }
+            };
+        }
+
+        let mut ran_point: [Point3; POINT_COUNT] = [Point3::default(); POINT_COUNT];
+        for point in ran_point.iter_mut() {
+            *point = Point3::new(rng.gen(), rng.gen(), rng.gen());
         }
 
         Perlin {
             ran_vec,
-            perm_x: Self::perlin_generate_perm(),
-            perm_y: Self::perlin_generate_perm(),
-            perm_z: Self::perlin_generate_perm(),
+            ran_point,
+            perm_x: Self::perlin_generate_perm(rng),
+            perm_y: Self::perlin_generate_perm(rng),
+            perm_z: Self::perlin_generate_perm(rng),
         }
     }
 
+    fn hash(&self, i: i64, j: i64, k: i64) -> usize {
+        (self.perm_x[(i & 255) as usize]
+            ^ self.perm_y[(j & 255) as usize]
+            ^ self.perm_z[(k & 255) as usize]) as usize
+    }
+
     pub fn noise(&self, p: Point3) -> f64 {
         let u = p.x - p.x.floor();
         let v = p.y - p.y.floor();
@@ -42,26 +71,20 @@ impl Perlin {
         let mut c: [[[Vec3; 2]; 2]; 2] = [[[Vec3::default(); 2]; 2]; 2];
 
         for ((di, dj), dk) in (0..2).cartesian_product(0..2).cartesian_product(0..2) {
-            let vec_index = (self.perm_x[((i + di as i64) & 255) as usize]
-                ^ self.perm_y[((j + dj as i64) & 255) as usize]
-                ^ self.perm_z[((k + dk as i64) & 255) as usize])
-                as usize;
-
-            c[di][dj][dk] = self.ran_vec[vec_index];
+            c[di][dj][dk] = self.ran_vec[self.hash(i + di as i64, j + dj as i64, k + dk as i64)];
         }
 
         Self::trilinear_interp(&c, u, v, w)
     }
 
-    fn perlin_generate_perm() -> [i64; POINT_COUNT] {
-        let mut rng = thread_rng();
+    fn perlin_generate_perm(rng: &mut impl Rng) -> [i64; POINT_COUNT] {
         let mut perm = [0i64; POINT_COUNT];
 
         for (i, p) in perm.iter_mut().enumerate() {
             *p = i as i64;
         }
 
-        perm.shuffle(&mut rng);
+        perm.shuffle(rng);
         perm
     }
 
@@ -94,16 +117,65 @@ impl Perlin {
     }
 
     pub fn turb(&self, p: Point3, depth: i64) -> f64 {
+        self.fbm(p, depth).abs()
+    }
+
+    /// Fractal Brownian motion, octaves of noise each at twice the frequency and half
+    /// the weight of the last
+    pub fn fbm(&self, p: Point3, octaves: i64) -> f64 {
         let mut accum = 0.0;
         let mut temp_p = p;
         let mut weight = 1.0;
 
-        for _ in 0..depth {
+        for _ in 0..octaves {
             accum += weight * self.noise(temp_p);
             weight *= 0.5;
             temp_p *= 2.0;
         }
 
-        accum.abs()
+        accum
+    }
+
+    /// Octaves of inverted, squared noise with sharp creases where the noise crosses zero,
+    /// normalised to [0, 1]
+    pub fn ridged(&self, p: Point3, octaves: i64) -> f64 {
+        let mut accum = 0.0;
+        let mut total = 0.0;
+        let mut temp_p = p;
+        let mut weight = 1.0;
+
+        for _ in 0..octaves {
+            let ridge = 1.0 - self.noise(temp_p).abs();
+            accum += weight * ridge * ridge;
+            total += weight;
+            weight *= 0.5;
+            temp_p *= 2.0;
+        }
+
+        if total > 0.0 {
+            accum / total
+        } else {
+            0.0
+        }
+    }
+
+    /// Distances to the nearest and second nearest of one random point per unit cell
+    pub fn worley(&self, p: Point3) -> (f64, f64) {
+        let cell = p.floor();
+        let mut nearest = (f64::INFINITY, f64::INFINITY);
+
+        for ((di, dj), dk) in (-1..=1).cartesian_product(-1..=1).cartesian_product(-1..=1) {
+            let neighbour = cell + Vec3::new(di as f64, dj as f64, dk as f64);
+            let index = self.hash(neighbour.x as i64, neighbour.y as i64, neighbour.z as i64);
+            let distance = (neighbour + self.ran_point[index]).distance(p);
+
+            if distance < nearest.0 {
+                nearest = (distance, nearest.0);
+            } else if distance < nearest.1 {
+                nearest.1 = distance;
+            }
+        }
+
+        nearest
     }
 }
@@ -1,65 +1,155 @@
 use crate::{
-    camera::Camera, hittable::Hittable, ray::Ray, utils::random_double, vec3::Color, MAX_DEPTH,
-    SAMPLE_PER_PIXELS,
+    background::{Background, SolidBackground},
+    hittable::Hittable,
+    material::Scatter,
+    pdf::{HittablePdf, MixturePdf, Pdf},
+    ray::Ray,
+    scene::Scene,
+    utils::random_double,
+    vec3::Color,
 };
 
 use rayon::prelude::*;
-use std::ops::{Div, Mul};
+use std::{ops::Div, sync::Arc};
 
-// TODO: Make this a paramater for scene? Scene struct?
-lazy_static! {
-    static ref BACKGROUND: Color = Color::new(0.0, 0.0, 0.0);
+#[derive(Clone)]
+pub struct RenderSettings {
+    pub samples_per_pixel: u32,
+    /// Maximum number of bounces before a ray contributes no more light
+    pub max_depth: u32,
+    /// Lights rays that escape the scene
+    pub background: Arc<dyn Background>,
 }
 
-pub fn ray_color(ray: &Ray, world: &dyn Hittable, depth: u32) -> Color {
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            samples_per_pixel: 50,
+            max_depth: 50,
+            background: Arc::new(SolidBackground {
+                color: Color::new(0.0, 0.0, 0.0),
+            }),
+        }
+    }
+}
+
+pub fn ray_color(ray: &Ray, scene: &Scene, background: &dyn Background, depth: u32) -> Color {
     if depth == 0 {
         return Color::new(0.0, 0.0, 0.0);
     }
 
-    if let Some(hit_record) = world.hit(ray, 0.001, f64::INFINITY) {
+    let lights = &scene.lights;
+    if let Some(hit_record) = scene.world.hit(ray, 0.001, f64::INFINITY) {
         let emitted = hit_record
             .material
             .emitted(hit_record.u, hit_record.v, hit_record.point);
 
-        if let Some((attenuation, scattered)) = hit_record.material.scatter(ray, &hit_record) {
-            return emitted + attenuation * ray_color(&scattered, world, depth - 1);
-        } else {
+        let Some(scatter_record) = hit_record.material.scatter(ray, &hit_record) else {
             return emitted;
+        };
+
+        let attenuation = scatter_record.attenuation;
+        return match scatter_record.scatter {
+            Scatter::Specular(scattered) => {
+                emitted + attenuation * ray_color(&scattered, scene, background, depth - 1)
+            }
+            Scatter::Pdf(material_pdf) => {
+                // Split samples between the material and the lights for next event estimation
+                let light_pdf = HittablePdf::new(lights, hit_record.point, ray.time);
+                let mixture_pdf = MixturePdf::new(&light_pdf, material_pdf.as_ref());
+                let pdf: &dyn Pdf = match lights.is_empty() {
+                    true => material_pdf.as_ref(),
+                    false => &mixture_pdf,
+                };
+
+                let scattered = Ray::new(hit_record.point, pdf.generate(), ray.time);
+                let pdf_value = pdf.value(scattered.direction);
+                if pdf_value <= 0.0 {
+                    return emitted;
+                }
+
+                let material = &hit_record.material;
+                let scattering = material.scattering(ray, &hit_record, &scattered, attenuation);
+                let incoming = ray_color(&scattered, scene, background, depth - 1);
+
+                emitted + scattering * incoming / pdf_value
+            }
+        };
+    }
+
+    background.value(ray.direction)
+}
+
+/// Running sum of linear radiance samples for every pixel, rows stored top to bottom
+pub struct Accumulator {
+    width: u32,
+    height: u32,
+    sums: Vec<Color>,
+    pub samples: u32,
+}
+
+impl Accumulator {
+    pub fn new(width: u32, height: u32) -> Self {
+        Accumulator {
+            width,
+            height,
+            sums: vec![Color::new(0.0, 0.0, 0.0); (width * height) as usize],
+            samples: 0,
         }
     }
 
-    *BACKGROUND
+    /// Trace another `samples` rays through every pixel
+    pub fn add_pass(&mut self, scene: &Scene, settings: &RenderSettings, samples: u32) {
+        let (width, height) = (self.width, self.height);
+
+        self.sums
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(index, sum)| {
+                let i = index as u32 % width;
+                let j = height - 1 - index as u32 / width;
+
+                for _ in 0..samples {
+                    let u = (i as f64 + random_double(-1.0, 1.0)) / (width - 1) as f64;
+                    let v = (j as f64 + random_double(-1.0, 1.0)) / (height - 1) as f64;
+                    let ray = scene.camera.get_ray(u, v);
+                    *sum += ray_color(
+                        &ray,
+                        scene,
+                        settings.background.as_ref(),
+                        settings.max_depth,
+                    );
+                }
+            });
+
+        self.samples += samples;
+    }
+
+    /// Average of the samples so far as linear RGB
+    pub fn to_linear(&self) -> Vec<f32> {
+        let samples = self.samples.max(1) as f64;
+
+        self.sums
+            .par_iter()
+            .flat_map_iter(|sum| {
+                let pixel_color = sum.div(samples);
+                [
+                    pixel_color.x as f32,
+                    pixel_color.y as f32,
+                    pixel_color.z as f32,
+                ]
+            })
+            .collect()
+    }
 }
 
 pub fn render(
     image_width: u32,
     image_height: u32,
-    scene: &dyn Hittable,
-    camera: Camera,
-) -> Vec<u8> {
-    (0..image_height)
-        .into_par_iter()
-        .rev()
-        .flat_map(|x| (0..image_width).into_par_iter().map(move |y| (x, y)))
-        .flat_map(|(j, i)| {
-            let pixel_color = (0..SAMPLE_PER_PIXELS)
-                .map(|_| {
-                    let u = (i as f64 + random_double(-1.0, 1.0)) / (image_width - 1) as f64;
-                    let v = (j as f64 + random_double(-1.0, 1.0)) / (image_height - 1) as f64;
-                    let ray = camera.get_ray(u, v);
-                    ray_color(&ray, scene, MAX_DEPTH)
-                })
-                .fold(Color::new(0.0, 0.0, 0.0), |acc, e| acc + e)
-                .div(SAMPLE_PER_PIXELS as f64)
-                .powf(0.5)
-                .mul(256f64);
-
-            vec![
-                pixel_color.x as u8,
-                pixel_color.y as u8,
-                pixel_color.z as u8,
-                0xff,
-            ]
-        })
-        .collect()
+    scene: &Scene,
+    settings: &RenderSettings,
+) -> Vec<f32> {
+    let mut accumulator = Accumulator::new(image_width, image_height);
+    accumulator.add_pass(scene, settings, settings.samples_per_pixel);
+    accumulator.to_linear()
 }
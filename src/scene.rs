@@ -1,23 +1,90 @@
 // TODO: Trait??
 
-use std::sync::Arc;
-
-use image::{io::Reader, ColorType};
+use std::{error::Error, sync::Arc};
 
 use crate::{
+    background::SkyBackground,
     camera::Camera,
     hittable::{
-        box_rect::BoxRect, bvh::Bvh, constant_medium::ConstantMedium, hittable_list::HittableList,
-        moving_sphere::MovingSphere, rotate_y::RotateY, sphere::Sphere, translate::Translate,
-        xy_rect::XYRect, xz_rect::XZRect, yz_rect::YZRect, Hittable,
+        box_rect::BoxRect,
+        bvh::{Bvh, BvhBuilder},
+        constant_medium::ConstantMedium,
+        hittable_list::HittableList,
+        moving_sphere::MovingSphere,
+        rotate_y::RotateY,
+        sphere::Sphere,
+        translate::Translate,
+        xy_rect::XYRect,
+        xz_rect::XZRect,
+        yz_rect::YZRect,
+        Hittable,
     },
     material::{Dielectric, DiffuseLight, Lambertian, Material, Metal},
-    texture::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor},
+    renderer::RenderSettings,
+    texture::{
+        CheckerTexture, ColorSpace, ImageTexture, NoiseTexture, SolidColor, TextureError, WrapMode,
+    },
     utils::{random_double, random_vec},
     vec3::{Color, Point3, Vec3, VecOps},
 };
 
-pub fn two_spheres(image_width: u32, image_height: u32) -> (HittableList, Camera) {
+pub struct Scene {
+    pub world: HittableList,
+    /// Emitters to explicitly sample, their materials are unused
+    pub lights: HittableList,
+    pub camera: Camera,
+    pub settings: RenderSettings,
+}
+
+/// When the camera's shutter opens and closes, in scene time. Animated objects are placed
+/// over this interval and camera rays spread across it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shutter {
+    pub open: f64,
+    pub close: f64,
+}
+
+impl Default for Shutter {
+    fn default() -> Self {
+        Shutter {
+            open: 0.0,
+            close: 1.0,
+        }
+    }
+}
+
+pub type SceneConstructor = fn(
+    image_width: u32,
+    image_height: u32,
+    shutter: Shutter,
+    bvh: BvhBuilder,
+) -> Result<Scene, Box<dyn Error>>;
+
+/// All built-in scenes, selectable by name from the command line
+pub const SCENES: &[(&str, SceneConstructor)] = &[
+    ("two_spheres", two_spheres),
+    ("two_perlin_spheres", two_perlin_spheres),
+    ("earth_scene", earth_scene),
+    ("light_scene", light_scene),
+    ("cornell_box", cornell_box),
+    ("smoke_cornell_box", smoke_cornell_box),
+    ("final_scene", final_scene),
+    ("random_scene", random_scene),
+];
+
+pub fn find_scene(name: &str) -> Option<SceneConstructor> {
+    SCENES
+        .iter()
+        .find(|(scene_name, _)| *scene_name == name)
+        .map(|(_, scene)| *scene)
+}
+
+pub fn two_spheres(
+    image_width: u32,
+    image_height: u32,
+    shutter: Shutter,
+    _bvh: BvhBuilder,
+) -> Result<Scene, Box<dyn Error>> {
     // Camera
     let lookfrom = Point3::new(13.0, 2.0, 3.0);
     let lookat = Point3::new(0.0, 0.0, 0.0);
@@ -33,8 +100,8 @@ pub fn two_spheres(image_width: u32, image_height: u32) -> (HittableList, Camera
         aspect_ratio,
         aperture,
         dist_to_focus,
-        0.0,
-        1.0,
+        shutter.open,
+        shutter.close,
     );
 
     let mut objects = HittableList::new();
@@ -59,10 +126,23 @@ pub fn two_spheres(image_width: u32, image_height: u32) -> (HittableList, Camera
         checker_material,
     )));
 
-    (objects, camera)
+    Ok(Scene {
+        world: objects,
+        lights: HittableList::new(),
+        camera,
+        settings: RenderSettings {
+            background: Arc::new(SkyBackground::default()),
+            ..Default::default()
+        },
+    })
 }
 
-pub fn two_perlin_spheres(image_width: u32, image_height: u32) -> (HittableList, Camera) {
+pub fn two_perlin_spheres(
+    image_width: u32,
+    image_height: u32,
+    shutter: Shutter,
+    _bvh: BvhBuilder,
+) -> Result<Scene, Box<dyn Error>> {
     // Camera
     let lookfrom = Point3::new(13.0, 2.0, 3.0);
     let lookat = Point3::new(0.0, 0.0, 0.0);
@@ -78,8 +158,8 @@ pub fn two_perlin_spheres(image_width: u32, image_height: u32) -> (HittableList,
         aspect_ratio,
         aperture,
         dist_to_focus,
-        0.0,
-        1.0,
+        shutter.open,
+        shutter.close,
     );
 
     let mut objects = HittableList::new();
@@ -101,10 +181,23 @@ pub fn two_perlin_spheres(image_width: u32, image_height: u32) -> (HittableList,
         perlin_material,
     )));
 
-    (objects, camera)
+    Ok(Scene {
+        world: objects,
+        lights: HittableList::new(),
+        camera,
+        settings: RenderSettings {
+            background: Arc::new(SkyBackground::default()),
+            ..Default::default()
+        },
+    })
 }
 
-pub fn earth_scene(image_width: u32, image_height: u32) -> (HittableList, Camera) {
+pub fn earth_scene(
+    image_width: u32,
+    image_height: u32,
+    shutter: Shutter,
+    _bvh: BvhBuilder,
+) -> Result<Scene, Box<dyn Error>> {
     // Camera
     let lookfrom = Point3::new(13.0, 2.0, 3.0);
     let lookat = Point3::new(0.0, 0.0, 0.0);
@@ -120,29 +213,13 @@ pub fn earth_scene(image_width: u32, image_height: u32) -> (HittableList, Camera
         aspect_ratio,
         aperture,
         dist_to_focus,
-        0.0,
-        1.0,
+        shutter.open,
+        shutter.close,
     );
 
     let mut objects = HittableList::new();
 
-    // TODO: Make helper function
-    // TODO: handle missing filename gracefully
-    let earth_image = Reader::open("./textures/earthmap.jpg")
-        .unwrap()
-        .decode()
-        .unwrap();
-
-    let image_width = earth_image.width();
-    let image_height = earth_image.height();
-    let image_data = earth_image.into_rgba8().to_vec();
-
-    let earth_texture = Box::new(ImageTexture::new(
-        image_data,
-        image_width as usize,
-        image_height as usize,
-        ColorType::Rgba8.bytes_per_pixel().into(),
-    ));
+    let earth_texture = Box::new(earth_texture()?);
 
     let earth_material: Arc<dyn Material> = Arc::new(Lambertian {
         albedo: earth_texture,
@@ -154,10 +231,23 @@ pub fn earth_scene(image_width: u32, image_height: u32) -> (HittableList, Camera
         earth_material.clone(),
     )));
 
-    (objects, camera)
+    Ok(Scene {
+        world: objects,
+        lights: HittableList::new(),
+        camera,
+        settings: RenderSettings {
+            background: Arc::new(SkyBackground::default()),
+            ..Default::default()
+        },
+    })
 }
 
-pub fn light_scene(image_width: u32, image_height: u32) -> (HittableList, Camera) {
+pub fn light_scene(
+    image_width: u32,
+    image_height: u32,
+    shutter: Shutter,
+    _bvh: BvhBuilder,
+) -> Result<Scene, Box<dyn Error>> {
     // Camera
     let lookfrom = Point3::new(26.0, 3.0, 6.0);
     let lookat = Point3::new(0.0, 2.0, 0.0);
@@ -174,8 +264,8 @@ pub fn light_scene(image_width: u32, image_height: u32) -> (HittableList, Camera
         aspect_ratio,
         aperture,
         dist_to_focus,
-        0.0,
-        1.0,
+        shutter.open,
+        shutter.close,
     );
 
     let mut objects = HittableList::new();
@@ -215,10 +305,36 @@ pub fn light_scene(image_width: u32, image_height: u32) -> (HittableList, Camera
         red_light,
     )));
 
-    (objects, camera)
+    let lights: HittableList = vec![
+        Box::new(XYRect::new(
+            3.0,
+            5.0,
+            1.0,
+            3.0,
+            -2.0,
+            Arc::clone(&diffuse_light),
+        )),
+        Box::new(Sphere::new(
+            Point3::new(0.0, 8.0, 0.0),
+            2.0,
+            Arc::clone(&diffuse_light),
+        )),
+    ];
+
+    Ok(Scene {
+        world: objects,
+        lights,
+        camera,
+        settings: RenderSettings::default(),
+    })
 }
 
-pub fn cornell_box(image_width: u32, image_height: u32) -> (HittableList, Camera) {
+pub fn cornell_box(
+    image_width: u32,
+    image_height: u32,
+    shutter: Shutter,
+    _bvh: BvhBuilder,
+) -> Result<Scene, Box<dyn Error>> {
     // Camera
     let lookfrom = Point3::new(278.0, 278.0, -800.0);
     let lookat = Point3::new(278.0, 278.0, 0.0);
@@ -235,8 +351,8 @@ pub fn cornell_box(image_width: u32, image_height: u32) -> (HittableList, Camera
         aspect_ratio,
         aperture,
         dist_to_focus,
-        0.0,
-        1.0,
+        shutter.open,
+        shutter.close,
     );
 
     let mut objects = HittableList::new();
@@ -325,10 +441,29 @@ pub fn cornell_box(image_width: u32, image_height: u32) -> (HittableList, Camera
     box2 = Box::new(Translate::new(box2, Vec3::new(130.0, 0.0, 65.0)));
     objects.push(box2);
 
-    (objects, camera)
+    let lights: HittableList = vec![Box::new(XZRect::new(
+        213.0,
+        343.0,
+        227.0,
+        332.0,
+        554.0,
+        Arc::clone(&light_material),
+    ))];
+
+    Ok(Scene {
+        world: objects,
+        lights,
+        camera,
+        settings: RenderSettings::default(),
+    })
 }
 
-pub fn smoke_cornell_box(image_width: u32, image_height: u32) -> (HittableList, Camera) {
+pub fn smoke_cornell_box(
+    image_width: u32,
+    image_height: u32,
+    shutter: Shutter,
+    _bvh: BvhBuilder,
+) -> Result<Scene, Box<dyn Error>> {
     // Camera
     let lookfrom = Point3::new(278.0, 278.0, -800.0);
     let lookat = Point3::new(278.0, 278.0, 0.0);
@@ -345,8 +480,8 @@ pub fn smoke_cornell_box(image_width: u32, image_height: u32) -> (HittableList,
         aspect_ratio,
         aperture,
         dist_to_focus,
-        0.0,
-        1.0,
+        shutter.open,
+        shutter.close,
     );
 
     let mut objects = HittableList::new();
@@ -436,10 +571,29 @@ pub fn smoke_cornell_box(image_width: u32, image_height: u32) -> (HittableList,
     box2 = Box::new(ConstantMedium::new(box2, 0.01, Color::new(1.0, 1.0, 1.0)));
     objects.push(box2);
 
-    (objects, camera)
+    let lights: HittableList = vec![Box::new(XZRect::new(
+        113.0,
+        443.0,
+        127.0,
+        432.0,
+        554.0,
+        Arc::clone(&light_material),
+    ))];
+
+    Ok(Scene {
+        world: objects,
+        lights,
+        camera,
+        settings: RenderSettings::default(),
+    })
 }
 
-pub fn final_scene(image_width: u32, image_height: u32) -> (HittableList, Camera) {
+pub fn final_scene(
+    image_width: u32,
+    image_height: u32,
+    shutter: Shutter,
+    bvh: BvhBuilder,
+) -> Result<Scene, Box<dyn Error>> {
     // Camera
     let lookfrom = Point3::new(478.0, 278.0, -600.0);
     let lookat = Point3::new(278.0, 278.0, 0.0);
@@ -456,8 +610,8 @@ pub fn final_scene(image_width: u32, image_height: u32) -> (HittableList, Camera
         aspect_ratio,
         aperture,
         dist_to_focus,
-        0.0,
-        1.0,
+        shutter.open,
+        shutter.close,
     );
 
     let mut boxes = HittableList::new();
@@ -488,7 +642,7 @@ pub fn final_scene(image_width: u32, image_height: u32) -> (HittableList, Camera
 
     let mut objects = HittableList::new();
 
-    objects.push(Box::new(Bvh::new(boxes, 0.0, 1.0)));
+    objects.push(Box::new(Bvh::new(boxes, shutter.open, shutter.close, bvh)));
 
     let light_material: Arc<dyn Material> = Arc::new(DiffuseLight::new(Color::new(7.0, 7.0, 7.0)));
     objects.push(Box::new(XZRect::new(
@@ -517,28 +671,25 @@ pub fn final_scene(image_width: u32, image_height: u32) -> (HittableList, Camera
     objects.push(Box::new(Sphere::new(
         Point3::new(260.0, 150.0, 45.0),
         50.0,
-        Arc::new(Dielectric { ir: 1.5 }),
+        Arc::new(Dielectric::new(1.5)),
     )));
 
     objects.push(Box::new(Sphere::new(
         Point3::new(0.0, 150.0, 145.0),
         50.0,
-        Arc::new(Metal {
-            albedo: Color::new(0.8, 0.8, 0.9),
-            fuzz: 1.0,
-        }),
+        Arc::new(Metal::new(Color::new(0.8, 0.8, 0.9), 1.0)),
     )));
 
     objects.push(Box::new(Sphere::new(
         Point3::new(360.0, 150.0, 145.0),
         70.0,
-        Arc::new(Dielectric { ir: 1.5 }),
+        Arc::new(Dielectric::new(1.5)),
     )));
     objects.push(Box::new(ConstantMedium::new(
         Box::new(Sphere::new(
             Point3::new(360.0, 150.0, 145.0),
             70.0,
-            Arc::new(Dielectric { ir: 1.5 }),
+            Arc::new(Dielectric::new(1.5)),
         )),
         0.2,
         Color::new(0.2, 0.4, 0.9),
@@ -548,7 +699,7 @@ pub fn final_scene(image_width: u32, image_height: u32) -> (HittableList, Camera
     let boundary = Box::new(Sphere::new(
         Point3::new(0.0, 0.0, 0.0),
         5000.0,
-        Arc::new(Dielectric { ir: 1.5 }),
+        Arc::new(Dielectric::new(1.5)),
     ));
     objects.push(Box::new(ConstantMedium::new(
         boundary,
@@ -556,23 +707,7 @@ pub fn final_scene(image_width: u32, image_height: u32) -> (HittableList, Camera
         Color::new(1.0, 1.0, 1.0),
     )));
 
-    // TODO: Make helper function
-    // TODO: handle missing filename gracefully
-    let earth_image = Reader::open("./textures/earthmap.jpg")
-        .unwrap()
-        .decode()
-        .unwrap();
-
-    let image_width = earth_image.width();
-    let image_height = earth_image.height();
-    let image_data = earth_image.into_rgba8().to_vec();
-
-    let earth_texture = Box::new(ImageTexture::new(
-        image_data,
-        image_width as usize,
-        image_height as usize,
-        ColorType::Rgba8.bytes_per_pixel().into(),
-    ));
+    let earth_texture = Box::new(earth_texture()?);
 
     let emat = Arc::new(Lambertian {
         albedo: earth_texture,
@@ -606,14 +741,36 @@ pub fn final_scene(image_width: u32, image_height: u32) -> (HittableList, Camera
     }
 
     objects.push(Box::new(Translate::new(
-        Box::new(RotateY::new(Box::new(Bvh::new(boxes, 0.0, 1.0)), 15.0)),
+        Box::new(RotateY::new(
+            Box::new(Bvh::new(boxes, shutter.open, shutter.close, bvh)),
+            15.0,
+        )),
         Vec3::new(-100.0, 270.0, 395.0),
     )));
 
-    (objects, camera)
+    let lights: HittableList = vec![Box::new(XZRect::new(
+        123.0,
+        432.0,
+        147.0,
+        412.0,
+        554.0,
+        Arc::clone(&light_material),
+    ))];
+
+    Ok(Scene {
+        world: objects,
+        lights,
+        camera,
+        settings: RenderSettings::default(),
+    })
 }
 
-pub fn random_scene(image_width: u32, image_height: u32) -> (Bvh, Camera) {
+pub fn random_scene(
+    image_width: u32,
+    image_height: u32,
+    shutter: Shutter,
+    bvh: BvhBuilder,
+) -> Result<Scene, Box<dyn Error>> {
     // Camera
     let lookfrom = Point3::new(13.0, 2.0, 3.0);
     let lookat = Point3::new(0.0, 0.0, 0.0);
@@ -630,8 +787,8 @@ pub fn random_scene(image_width: u32, image_height: u32) -> (Bvh, Camera) {
         aspect_ratio,
         aperture,
         dist_to_focus,
-        0.0,
-        1.0,
+        shutter.open,
+        shutter.close,
     );
 
     let mut world: HittableList = Vec::new();
@@ -650,7 +807,7 @@ pub fn random_scene(image_width: u32, image_height: u32) -> (Bvh, Camera) {
         ground_material,
     )));
 
-    let glass_material: Arc<dyn Material> = Arc::new(Dielectric { ir: 1.5 });
+    let glass_material: Arc<dyn Material> = Arc::new(Dielectric::new(1.5));
 
     for a in -11..11 {
         for b in -11..11 {
@@ -683,7 +840,7 @@ pub fn random_scene(image_width: u32, image_height: u32) -> (Bvh, Camera) {
                 } else if choose_mat < 0.95 {
                     let albedo = Vec3::random_color();
                     let fuzz = random_double(0.0, 0.5);
-                    sphere_material = Arc::new(Metal { albedo, fuzz });
+                    sphere_material = Arc::new(Metal::new(albedo, fuzz));
                     world.push(Box::new(Sphere::new(centre, 0.2, sphere_material.clone())));
                 } else {
                     world.push(Box::new(Sphere::new(centre, 0.2, glass_material.clone())));
@@ -707,15 +864,26 @@ pub fn random_scene(image_width: u32, image_height: u32) -> (Bvh, Camera) {
         big_lamb,
     )));
 
-    let big_metal = Arc::new(Metal {
-        albedo: Color::new(0.7, 0.6, 0.5),
-        fuzz: 0.0,
-    });
+    let big_metal = Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
     world.push(Box::new(Sphere::new(
         Point3::new(4.0, 1.0, 0.0),
         1.0,
         big_metal,
     )));
 
-    (Bvh::new(world, 0.0, 1.0), camera)
+    Ok(Scene {
+        world: vec![Box::new(Bvh::new(world, shutter.open, shutter.close, bvh))],
+        lights: HittableList::new(),
+        camera,
+        settings: RenderSettings {
+            background: Arc::new(SkyBackground::default()),
+            ..Default::default()
+        },
+    })
+}
+
+/// Wraps around the globe, but not over the poles
+fn earth_texture() -> Result<ImageTexture, TextureError> {
+    let texture = ImageTexture::open("./textures/earthmap.jpg", ColorSpace::Srgb)?;
+    Ok(texture.with_wrap(WrapMode::Repeat, WrapMode::Clamp))
 }
@@ -0,0 +1,1058 @@
+use std::{collections::HashMap, error::Error, fs, sync::Arc};
+
+use glam::{DMat4, DQuat};
+use serde::Deserialize;
+
+use crate::{
+    background::{Background, EnvironmentMap, SkyBackground, SolidBackground},
+    camera::{Camera, CameraKeyframe, CameraSettings},
+    gltf_scene::{framing_camera, load_gltf},
+    hittable::{
+        animated_transform::{AnimatedTransform, Keyframe},
+        box_rect::BoxRect,
+        bvh::{Bvh, BvhBuilder},
+        constant_medium::ConstantMedium,
+        cutout::{AlphaMode, Cutout},
+        hittable_list::HittableList,
+        moving_sphere::MovingSphere,
+        rotate_y::RotateY,
+        sphere::Sphere,
+        transform::Transform,
+        translate::Translate,
+        triangle::Triangle,
+        triangle_mesh::TriangleMesh,
+        xy_rect::XYRect,
+        xz_rect::XZRect,
+        yz_rect::YZRect,
+        Hittable,
+    },
+    material::{
+        BumpMap, Dielectric, DiffuseLight, Isotropic, Lambertian, Material, Metal, Microfacet,
+        NormalMap, Principled,
+    },
+    renderer::RenderSettings,
+    scene::{Scene, Shutter},
+    texture::{
+        CheckerTexture, ColorRamp, ColorSpace, Filter, ImageTexture, NoisePattern, NoiseTexture,
+        SolidColor, Texture, WrapMode,
+    },
+    vec3::{Color, Point3, Vec3},
+};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    camera: CameraDef,
+    #[serde(default)]
+    settings: SettingsDef,
+    #[serde(default)]
+    materials: HashMap<String, MaterialDef>,
+    /// Objects built once and placed any number of times by `instance` objects
+    #[serde(default)]
+    meshes: HashMap<String, ObjectDef>,
+    #[serde(default)]
+    objects: Vec<ObjectDef>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDef {
+    lookfrom: [f64; 3],
+    lookat: [f64; 3],
+    #[serde(default = "default_vup")]
+    vup: [f64; 3],
+    vfov: f64,
+    #[serde(default)]
+    aperture: f64,
+    #[serde(default = "default_focus_dist")]
+    focus_dist: f64,
+    #[serde(default)]
+    time0: f64,
+    #[serde(default = "default_time1")]
+    time1: f64,
+    /// Poses to animate between over a sequence of frames
+    #[serde(default)]
+    keyframes: Vec<CameraKeyframeDef>,
+}
+
+/// Anything left out of a keyframe keeps the camera's own value
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraKeyframeDef {
+    time: f64,
+    lookfrom: Option<[f64; 3]>,
+    lookat: Option<[f64; 3]>,
+    vup: Option<[f64; 3]>,
+    vfov: Option<f64>,
+    aperture: Option<f64>,
+    focus_dist: Option<f64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct SettingsDef {
+    samples_per_pixel: Option<u32>,
+    max_depth: Option<u32>,
+    background: Option<BackgroundDef>,
+}
+
+/// Either a plain `[r, g, b]` color or a background table
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BackgroundDef {
+    Color([f64; 3]),
+    Background(BackgroundKind),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum BackgroundKind {
+    Solid {
+        color: [f64; 3],
+    },
+    Sky {
+        horizon: Option<[f64; 3]>,
+        zenith: Option<[f64; 3]>,
+    },
+    Environment {
+        path: String,
+    },
+}
+
+fn default_vup() -> [f64; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+fn default_focus_dist() -> f64 {
+    10.0
+}
+
+fn default_time1() -> f64 {
+    1.0
+}
+
+/// Either a plain `[r, g, b]` color or a texture table
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TextureDef {
+    Color([f64; 3]),
+    Texture(TextureKind),
+}
+
+/// Either a plain number or a texture table, whose channels are averaged
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScalarDef {
+    Value(f64),
+    Texture(TextureKind),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureKind {
+    Solid {
+        color: [f64; 3],
+    },
+    Checker {
+        even: [f64; 3],
+        odd: [f64; 3],
+    },
+    Noise {
+        scale: f64,
+    },
+    /// Gray pattern of seeded Perlin or Worley noise, `scale` is the frequency of the noise
+    Procedural {
+        pattern: PatternDef,
+        #[serde(default = "default_noise_scale")]
+        scale: f64,
+        #[serde(default = "default_octaves")]
+        octaves: i64,
+        seed: Option<u64>,
+    },
+    /// Another texture's value mapped through a gradient of colors
+    ColorRamp {
+        input: Box<TextureKind>,
+        stops: Vec<RampStop>,
+    },
+    Image {
+        path: String,
+        #[serde(default)]
+        wrap: WrapMode,
+        #[serde(default)]
+        filter: Filter,
+        #[serde(default = "default_uv_scale")]
+        scale: [f64; 2],
+        #[serde(default)]
+        offset: [f64; 2],
+        /// Defaults to sRGB for colors and linear for scalar parameters and normal maps
+        color_space: Option<ColorSpace>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum PatternDef {
+    Fbm,
+    Turbulence,
+    Ridged,
+    Marble {
+        #[serde(default = "default_pattern_axis")]
+        axis: [f64; 3],
+        frequency: f64,
+        turbulence: f64,
+    },
+    Wood {
+        #[serde(default = "default_pattern_axis")]
+        axis: [f64; 3],
+        frequency: f64,
+        turbulence: f64,
+    },
+    Cells,
+    CellEdges,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RampStop {
+    at: f64,
+    color: [f64; 3],
+}
+
+fn default_noise_scale() -> f64 {
+    1.0
+}
+
+fn default_octaves() -> i64 {
+    NoiseTexture::DEFAULT_OCTAVES
+}
+
+fn default_pattern_axis() -> [f64; 3] {
+    [0.0, 0.0, 1.0]
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDef {
+    Lambertian {
+        albedo: TextureDef,
+    },
+    Metal {
+        albedo: TextureDef,
+        fuzz: ScalarDef,
+    },
+    Microfacet {
+        base_color: TextureDef,
+        metallic: Option<ScalarDef>,
+        roughness: ScalarDef,
+    },
+    Principled(Box<PrincipledDef>),
+    Dielectric {
+        ir: ScalarDef,
+    },
+    DiffuseLight {
+        emit: TextureDef,
+    },
+    Isotropic {
+        albedo: TextureDef,
+    },
+    /// Another material, given inline, with its normal tilted by a height texture
+    BumpMap {
+        material: Box<MaterialDef>,
+        height: ScalarDef,
+        scale: f64,
+    },
+    /// Another material, given inline, with its normal read from a tangent-space normal map
+    NormalMap {
+        material: Box<MaterialDef>,
+        normals: TextureDef,
+        #[serde(default = "default_strength")]
+        strength: f64,
+    },
+}
+
+fn default_uv_scale() -> [f64; 2] {
+    [1.0, 1.0]
+}
+
+fn default_strength() -> f64 {
+    1.0
+}
+
+/// Parameters left out keep the defaults of `Principled::new`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PrincipledDef {
+    base_color: TextureDef,
+    metallic: Option<ScalarDef>,
+    roughness: Option<ScalarDef>,
+    specular: Option<ScalarDef>,
+    clearcoat: Option<ScalarDef>,
+    clearcoat_roughness: Option<ScalarDef>,
+    sheen: Option<ScalarDef>,
+    transmission: Option<ScalarDef>,
+    ir: Option<ScalarDef>,
+}
+
+/// Can't deny unknown fields itself, as its shape is flattened into it, so `ShapeDef`
+/// rejects any key that neither it nor the shape takes
+#[derive(Deserialize)]
+struct ObjectDef {
+    #[serde(flatten)]
+    shape: ShapeDef,
+    /// Scale along the x, y and z axes, applied first
+    scale: Option<[f64; 3]>,
+    /// Rotation about the x, y then z axes in degrees, applied after `scale`
+    rotate: Option<[f64; 3]>,
+    /// Rotation about the y-axis in degrees, applied before `translate`
+    rotate_y: Option<f64>,
+    translate: Option<[f64; 3]>,
+    /// Animation applied on top of the transforms above, interpolated by ray time
+    #[serde(default)]
+    keyframes: Vec<KeyframeDef>,
+    /// Also add this object to the scene's explicitly sampled lights, only spheres and
+    /// rectangles can be
+    #[serde(default)]
+    light: bool,
+    /// Holes where a texture's alpha channel is transparent
+    alpha: Option<AlphaDef>,
+}
+
+/// Cut holes where the alpha of `texture` is below `threshold`, or at random by alpha
+/// if `stochastic`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AlphaDef {
+    texture: TextureDef,
+    #[serde(default = "default_threshold")]
+    threshold: f64,
+    #[serde(default)]
+    stochastic: bool,
+}
+
+fn default_threshold() -> f64 {
+    0.5
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyframeDef {
+    time: f64,
+    #[serde(default)]
+    translate: [f64; 3],
+    /// Rotation about the x, y then z axes in degrees
+    #[serde(default)]
+    rotate: [f64; 3],
+    #[serde(default = "default_scale")]
+    scale: [f64; 3],
+}
+
+fn default_scale() -> [f64; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ShapeDef {
+    Sphere {
+        centre: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    MovingSphere {
+        centre0: [f64; 3],
+        centre1: [f64; 3],
+        time0: f64,
+        time1: f64,
+        radius: f64,
+        material: String,
+    },
+    XyRect {
+        x0: f64,
+        x1: f64,
+        y0: f64,
+        y1: f64,
+        k: f64,
+        material: String,
+    },
+    XzRect {
+        x0: f64,
+        x1: f64,
+        z0: f64,
+        z1: f64,
+        k: f64,
+        material: String,
+    },
+    YzRect {
+        y0: f64,
+        y1: f64,
+        z0: f64,
+        z1: f64,
+        k: f64,
+        material: String,
+    },
+    Box {
+        min: [f64; 3],
+        max: [f64; 3],
+        material: String,
+    },
+    ConstantMedium {
+        boundary: Box<ObjectDef>,
+        density: f64,
+        albedo: [f64; 3],
+    },
+    Triangle {
+        vertices: [[f64; 3]; 3],
+        material: String,
+    },
+    /// Wavefront OBJ file, faces without an MTL material use `material` or plain white
+    Mesh {
+        path: String,
+        material: Option<String>,
+    },
+    /// glTF 2.0 file (.gltf or .glb), its cameras and lights are ignored
+    Gltf {
+        path: String,
+    },
+    Bvh {
+        objects: Vec<ObjectDef>,
+    },
+    /// One of the scene's `meshes`, shared with its other instances
+    Instance {
+        mesh: String,
+    },
+}
+
+type Materials = HashMap<String, Arc<dyn Material>>;
+type Meshes = HashMap<String, Arc<dyn Hittable>>;
+
+/// Load a scene description file (TOML), see `scenes/` for examples, or a glTF 2.0 file.
+/// Without a `shutter` the camera's own `time0` and `time1` are used.
+pub fn load_scene(
+    path: &str,
+    image_width: u32,
+    image_height: u32,
+    shutter: Option<Shutter>,
+    bvh: BvhBuilder,
+) -> Result<Scene, Box<dyn Error>> {
+    let aspect_ratio = image_width as f64 / image_height as f64;
+    if is_gltf(path) {
+        return build_gltf_scene(path, aspect_ratio, shutter.unwrap_or_default(), bvh);
+    }
+
+    let contents = fs::read_to_string(path)?;
+    let scene_file: SceneFile = toml::from_str(&contents)?;
+
+    let shutter = shutter.unwrap_or(Shutter {
+        open: scene_file.camera.time0,
+        close: scene_file.camera.time1,
+    });
+    let camera = build_camera(&scene_file.camera, aspect_ratio, shutter);
+
+    let mut materials = Materials::new();
+    for (name, material) in &scene_file.materials {
+        materials.insert(name.clone(), build_material(material)?);
+    }
+
+    let times = (shutter.open, shutter.close);
+    // Meshes can't instance each other
+    let mut meshes = Meshes::new();
+    for (name, mesh) in &scene_file.meshes {
+        let mesh = build_object(mesh, &materials, &Meshes::new(), times, bvh)?;
+        meshes.insert(name.clone(), Arc::from(mesh));
+    }
+
+    let mut world = HittableList::new();
+    let mut lights = HittableList::new();
+    for object in &scene_file.objects {
+        if !object.light {
+            world.push(build_object(object, &materials, &meshes, times, bvh)?);
+            continue;
+        }
+
+        if !is_sampleable(&object.shape) {
+            return Err("Only spheres and rectangles can be lights".into());
+        }
+
+        // Shared rather than built twice, so the world and the lights can't disagree
+        let light: Arc<dyn Hittable> =
+            Arc::from(build_object(object, &materials, &meshes, times, bvh)?);
+        world.push(Box::new(Arc::clone(&light)));
+        lights.push(Box::new(light));
+    }
+
+    Ok(Scene {
+        world,
+        lights,
+        camera,
+        settings: build_settings(&scene_file.settings)?,
+    })
+}
+
+/// Shapes that can aim rays at themselves, wrapped in any of the object's transforms
+fn is_sampleable(shape: &ShapeDef) -> bool {
+    matches!(
+        shape,
+        ShapeDef::Sphere { .. }
+            | ShapeDef::XyRect { .. }
+            | ShapeDef::XzRect { .. }
+            | ShapeDef::YzRect { .. }
+    )
+}
+
+fn is_gltf(path: &str) -> bool {
+    let path = path.to_lowercase();
+    path.ends_with(".gltf") || path.ends_with(".glb")
+}
+
+/// A glTF file on its own, seen from its first camera or from the front if it has none
+fn build_gltf_scene(
+    path: &str,
+    aspect_ratio: f64,
+    shutter: Shutter,
+    bvh: BvhBuilder,
+) -> Result<Scene, Box<dyn Error>> {
+    let gltf = load_gltf(path, aspect_ratio, bvh)?;
+    let camera = gltf
+        .camera
+        .unwrap_or_else(|| framing_camera(&gltf.objects, aspect_ratio));
+    let camera = CameraSettings {
+        time0: shutter.open,
+        time1: shutter.close,
+        ..camera.settings
+    }
+    .build();
+
+    Ok(Scene {
+        world: vec![Box::new(gltf.objects) as Box<dyn Hittable>],
+        lights: HittableList::new(),
+        camera,
+        settings: RenderSettings {
+            background: Arc::new(SkyBackground::default()),
+            ..Default::default()
+        },
+    })
+}
+
+fn build_settings(settings: &SettingsDef) -> Result<RenderSettings, Box<dyn Error>> {
+    let mut render_settings = RenderSettings::default();
+    if let Some(samples_per_pixel) = settings.samples_per_pixel {
+        render_settings.samples_per_pixel = samples_per_pixel;
+    }
+    if let Some(max_depth) = settings.max_depth {
+        render_settings.max_depth = max_depth;
+    }
+    if let Some(background) = &settings.background {
+        render_settings.background = build_background(background)?;
+    }
+
+    Ok(render_settings)
+}
+
+fn build_background(background: &BackgroundDef) -> Result<Arc<dyn Background>, Box<dyn Error>> {
+    let kind = match background {
+        BackgroundDef::Color(color) => {
+            return Ok(Arc::new(SolidBackground {
+                color: Color::from(*color),
+            }))
+        }
+        BackgroundDef::Background(kind) => kind,
+    };
+
+    Ok(match kind {
+        BackgroundKind::Solid { color } => Arc::new(SolidBackground {
+            color: Color::from(*color),
+        }),
+        BackgroundKind::Sky { horizon, zenith } => {
+            let mut sky = SkyBackground::default();
+            if let Some(horizon) = horizon {
+                sky.horizon = Color::from(*horizon);
+            }
+            if let Some(zenith) = zenith {
+                sky.zenith = Color::from(*zenith);
+            }
+            Arc::new(sky)
+        }
+        BackgroundKind::Environment { path } => Arc::new(EnvironmentMap::open(path)?),
+    })
+}
+
+fn build_camera(camera: &CameraDef, aspect_ratio: f64, shutter: Shutter) -> Camera {
+    let settings = CameraSettings {
+        lookfrom: Point3::from(camera.lookfrom),
+        lookat: Point3::from(camera.lookat),
+        vup: Vec3::from(camera.vup),
+        vertical_fov: camera.vfov,
+        aspect_ratio,
+        aperture: camera.aperture,
+        focus_dist: camera.focus_dist,
+        time0: shutter.open,
+        time1: shutter.close,
+    };
+
+    if camera.keyframes.is_empty() {
+        return settings.build();
+    }
+
+    let mut keyframes: Vec<CameraKeyframe> = camera
+        .keyframes
+        .iter()
+        .map(|keyframe| CameraKeyframe {
+            time: keyframe.time,
+            settings: CameraSettings {
+                lookfrom: keyframe.lookfrom.map_or(settings.lookfrom, Point3::from),
+                lookat: keyframe.lookat.map_or(settings.lookat, Point3::from),
+                vup: keyframe.vup.map_or(settings.vup, Vec3::from),
+                vertical_fov: keyframe.vfov.unwrap_or(settings.vertical_fov),
+                aperture: keyframe.aperture.unwrap_or(settings.aperture),
+                focus_dist: keyframe.focus_dist.unwrap_or(settings.focus_dist),
+                ..settings
+            },
+        })
+        .collect();
+    keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+    settings.build_animated(keyframes)
+}
+
+fn build_texture(texture: &TextureDef) -> Result<Box<dyn Texture>, Box<dyn Error>> {
+    let kind = match texture {
+        TextureDef::Color(color) => {
+            return Ok(Box::new(SolidColor {
+                color: Vec3::from(*color),
+            }))
+        }
+        TextureDef::Texture(kind) => kind,
+    };
+
+    build_texture_kind(kind, ColorSpace::Srgb)
+}
+
+/// Images hold data rather than colors, such as normal maps
+fn build_data_texture(texture: &TextureDef) -> Result<Box<dyn Texture>, Box<dyn Error>> {
+    match texture {
+        TextureDef::Color(_) => build_texture(texture),
+        TextureDef::Texture(kind) => build_texture_kind(kind, ColorSpace::Linear),
+    }
+}
+
+/// `color_space` is used for images that don't set their own
+fn build_texture_kind(
+    kind: &TextureKind,
+    color_space: ColorSpace,
+) -> Result<Box<dyn Texture>, Box<dyn Error>> {
+    Ok(match kind {
+        TextureKind::Solid { color } => Box::new(SolidColor {
+            color: Vec3::from(*color),
+        }),
+        TextureKind::Checker { even, odd } => {
+            Box::new(CheckerTexture::new(Vec3::from(*even), Vec3::from(*odd)))
+        }
+        TextureKind::Noise { scale } => NoiseTexture::new_box(*scale),
+        TextureKind::Procedural {
+            pattern,
+            scale,
+            octaves,
+            seed,
+        } => {
+            if *octaves < 1 {
+                return Err("Procedural textures need at least one octave".into());
+            }
+
+            let texture = NoiseTexture::new(build_pattern(pattern)?)
+                .with_scale(*scale)
+                .with_octaves(*octaves);
+            match seed {
+                Some(seed) => Box::new(texture.with_seed(*seed)),
+                None => Box::new(texture),
+            }
+        }
+        TextureKind::ColorRamp { input, stops } => {
+            if stops.is_empty() {
+                return Err("Color ramps need at least one stop".into());
+            }
+
+            Box::new(ColorRamp::new(
+                build_texture_kind(input, ColorSpace::Linear)?,
+                stops
+                    .iter()
+                    .map(|stop| (stop.at, Vec3::from(stop.color)))
+                    .collect(),
+            ))
+        }
+        TextureKind::Image {
+            path,
+            wrap,
+            filter,
+            scale,
+            offset,
+            color_space: image_color_space,
+        } => Box::new(
+            ImageTexture::open(path, image_color_space.unwrap_or(color_space))?
+                .with_wrap(*wrap, *wrap)
+                .with_filter(*filter)
+                .with_transform((scale[0], scale[1]), (offset[0], offset[1])),
+        ),
+    })
+}
+
+fn build_pattern(pattern: &PatternDef) -> Result<NoisePattern, Box<dyn Error>> {
+    let unit = |axis: &[f64; 3]| -> Result<Vec3, Box<dyn Error>> {
+        Vec3::from(*axis)
+            .try_normalize()
+            .ok_or_else(|| "Noise pattern axis can't be zero".into())
+    };
+
+    Ok(match pattern {
+        PatternDef::Fbm => NoisePattern::Fbm,
+        PatternDef::Turbulence => NoisePattern::Turbulence,
+        PatternDef::Ridged => NoisePattern::Ridged,
+        PatternDef::Marble {
+            axis,
+            frequency,
+            turbulence,
+        } => NoisePattern::Marble {
+            axis: unit(axis)?,
+            frequency: *frequency,
+            turbulence: *turbulence,
+        },
+        PatternDef::Wood {
+            axis,
+            frequency,
+            turbulence,
+        } => NoisePattern::Wood {
+            axis: unit(axis)?,
+            frequency: *frequency,
+            turbulence: *turbulence,
+        },
+        PatternDef::Cells => NoisePattern::Cells,
+        PatternDef::CellEdges => NoisePattern::CellEdges,
+    })
+}
+
+fn build_scalar(scalar: &ScalarDef) -> Result<Box<dyn Texture>, Box<dyn Error>> {
+    match scalar {
+        ScalarDef::Value(value) => Ok(Box::new(SolidColor::splat(*value))),
+        ScalarDef::Texture(kind) => build_texture_kind(kind, ColorSpace::Linear),
+    }
+}
+
+fn build_material(material: &MaterialDef) -> Result<Arc<dyn Material>, Box<dyn Error>> {
+    Ok(match material {
+        MaterialDef::Lambertian { albedo } => Arc::new(Lambertian {
+            albedo: build_texture(albedo)?,
+        }),
+        MaterialDef::Metal { albedo, fuzz } => {
+            Arc::new(Metal::textured(build_texture(albedo)?, build_scalar(fuzz)?))
+        }
+        MaterialDef::Microfacet {
+            base_color,
+            metallic,
+            roughness,
+        } => Arc::new(Microfacet::textured(
+            build_texture(base_color)?,
+            match metallic {
+                Some(metallic) => build_scalar(metallic)?,
+                None => Box::new(SolidColor::splat(0.0)),
+            },
+            build_scalar(roughness)?,
+        )),
+        MaterialDef::Principled(def) => {
+            let PrincipledDef {
+                base_color,
+                metallic,
+                roughness,
+                specular,
+                clearcoat,
+                clearcoat_roughness,
+                sheen,
+                transmission,
+                ir,
+            } = def.as_ref();
+            let mut principled = Principled::new(Color::ONE);
+            principled.base_color = build_texture(base_color)?;
+
+            let parameters = [
+                (metallic, &mut principled.metallic),
+                (roughness, &mut principled.roughness),
+                (specular, &mut principled.specular),
+                (clearcoat, &mut principled.clearcoat),
+                (clearcoat_roughness, &mut principled.clearcoat_roughness),
+                (sheen, &mut principled.sheen),
+                (transmission, &mut principled.transmission),
+                (ir, &mut principled.ir),
+            ];
+            for (scalar, parameter) in parameters {
+                if let Some(scalar) = scalar {
+                    *parameter = build_scalar(scalar)?;
+                }
+            }
+
+            Arc::new(principled)
+        }
+        MaterialDef::Dielectric { ir } => Arc::new(Dielectric::textured(build_scalar(ir)?)),
+        MaterialDef::DiffuseLight { emit } => Arc::new(DiffuseLight {
+            emit: build_texture(emit)?,
+        }),
+        MaterialDef::Isotropic { albedo } => Arc::new(Isotropic {
+            albedo: build_texture(albedo)?,
+        }),
+        MaterialDef::BumpMap {
+            material,
+            height,
+            scale,
+        } => Arc::new(BumpMap::new(
+            build_material(material)?,
+            build_scalar(height)?,
+            *scale,
+        )),
+        MaterialDef::NormalMap {
+            material,
+            normals,
+            strength,
+        } => Arc::new(NormalMap::new(
+            build_material(material)?,
+            build_data_texture(normals)?,
+            *strength,
+        )),
+    })
+}
+
+fn lookup(materials: &Materials, name: &str) -> Result<Arc<dyn Material>, Box<dyn Error>> {
+    materials
+        .get(name)
+        .cloned()
+        .ok_or_else(|| format!("Unknown material \"{}\"", name).into())
+}
+
+fn build_object(
+    object: &ObjectDef,
+    materials: &Materials,
+    meshes: &Meshes,
+    times: (f64, f64),
+    bvh: BvhBuilder,
+) -> Result<Box<dyn Hittable>, Box<dyn Error>> {
+    let (time0, time1) = times;
+
+    let mut matrix = (object.scale.is_some() || object.rotate.is_some()).then(|| {
+        let scale = Vec3::from(object.scale.unwrap_or_else(default_scale));
+        let rotation = build_rotation(object.rotate.unwrap_or_default());
+        DMat4::from_quat(rotation) * DMat4::from_scale(scale)
+    });
+
+    let mut hittable: Box<dyn Hittable> = match &object.shape {
+        ShapeDef::Sphere {
+            centre,
+            radius,
+            material,
+        } => Box::new(Sphere::new(
+            Point3::from(*centre),
+            *radius,
+            lookup(materials, material)?,
+        )),
+        ShapeDef::MovingSphere {
+            centre0,
+            centre1,
+            time0,
+            time1,
+            radius,
+            material,
+        } => Box::new(MovingSphere::new(
+            Point3::from(*centre0),
+            Point3::from(*centre1),
+            *time0,
+            *time1,
+            *radius,
+            lookup(materials, material)?,
+        )),
+        ShapeDef::XyRect {
+            x0,
+            x1,
+            y0,
+            y1,
+            k,
+            material,
+        } => Box::new(XYRect::new(
+            *x0,
+            *x1,
+            *y0,
+            *y1,
+            *k,
+            lookup(materials, material)?,
+        )),
+        ShapeDef::XzRect {
+            x0,
+            x1,
+            z0,
+            z1,
+            k,
+            material,
+        } => Box::new(XZRect::new(
+            *x0,
+            *x1,
+            *z0,
+            *z1,
+            *k,
+            lookup(materials, material)?,
+        )),
+        ShapeDef::YzRect {
+            y0,
+            y1,
+            z0,
+            z1,
+            k,
+            material,
+        } => Box::new(YZRect::new(
+            *y0,
+            *y1,
+            *z0,
+            *z1,
+            *k,
+            lookup(materials, material)?,
+        )),
+        ShapeDef::Box { min, max, material } => Box::new(BoxRect::new(
+            Point3::from(*min),
+            Point3::from(*max),
+            lookup(materials, material)?,
+        )),
+        ShapeDef::ConstantMedium {
+            boundary,
+            density,
+            albedo,
+        } => Box::new(ConstantMedium::new(
+            build_object(boundary, materials, meshes, times, bvh)?,
+            *density,
+            Vec3::from(*albedo),
+        )),
+        ShapeDef::Triangle { vertices, material } => Box::new(Triangle::new(
+            vertices.map(Point3::from),
+            lookup(materials, material)?,
+        )),
+        ShapeDef::Mesh { path, material } => {
+            let default_material = match material {
+                Some(material) => lookup(materials, material)?,
+                None => Arc::new(Lambertian {
+                    albedo: Box::new(SolidColor::new(0.73, 0.73, 0.73)),
+                }),
+            };
+
+            Box::new(TriangleMesh::load_obj(path, default_material, bvh)?)
+        }
+        ShapeDef::Gltf { path } => Box::new(load_gltf(path, 1.0, bvh)?.objects),
+        ShapeDef::Bvh { objects } => {
+            let objects = objects
+                .iter()
+                .map(|object| build_object(object, materials, meshes, times, bvh))
+                .collect::<Result<HittableList, _>>()?;
+
+            if objects.is_empty() {
+                return Err("Bvh must contain at least one object".into());
+            }
+
+            Box::new(Bvh::new(objects, time0, time1, bvh))
+        }
+        ShapeDef::Instance { mesh } => {
+            let mesh = meshes
+                .get(mesh)
+                .ok_or_else(|| format!("Unknown mesh \"{}\"", mesh))?;
+
+            // The scale and rotation place the shared mesh itself, not a box around it
+            let matrix = matrix.take().unwrap_or(DMat4::IDENTITY);
+            Box::new(Transform::instance(Arc::clone(mesh), matrix))
+        }
+    };
+
+    if let Some(alpha) = &object.alpha {
+        let mode = match alpha.stochastic {
+            true => AlphaMode::Stochastic,
+            false => AlphaMode::Threshold(alpha.threshold),
+        };
+
+        hittable = Box::new(Cutout::new(
+            hittable,
+            Arc::from(build_texture(&alpha.texture)?),
+            mode,
+        ));
+    }
+
+    if let Some(matrix) = matrix {
+        hittable = Box::new(Transform::new(hittable, matrix));
+    }
+
+    if let Some(angle) = object.rotate_y {
+        hittable = Box::new(RotateY::new(hittable, angle));
+    }
+
+    if let Some(offset) = object.translate {
+        hittable = Box::new(Translate::new(hittable, Vec3::from(offset)));
+    }
+
+    if !object.keyframes.is_empty() {
+        let keyframes = object
+            .keyframes
+            .iter()
+            .map(|keyframe| {
+                Keyframe::new(
+                    keyframe.time,
+                    Vec3::from(keyframe.translate),
+                    build_rotation(keyframe.rotate),
+                    Vec3::from(keyframe.scale),
+                )
+            })
+            .collect();
+
+        hittable = Box::new(AnimatedTransform::new(hittable, keyframes));
+    }
+
+    Ok(hittable)
+}
+
+/// Rotation about the x, y then z axes, in degrees
+fn build_rotation(degrees: [f64; 3]) -> DQuat {
+    let [x, y, z] = degrees.map(f64::to_radians);
+    DQuat::from_rotation_z(z) * DQuat::from_rotation_y(y) * DQuat::from_rotation_x(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAMERA: &str =
+        "[camera]\nlookfrom = [0.0, 0.0, 5.0]\nlookat = [0.0, 0.0, 0.0]\nvfov = 40.0\n";
+
+    fn parse(objects: &str) -> Result<SceneFile, toml::de::Error> {
+        toml::from_str(&format!("{}{}", CAMERA, objects))
+    }
+
+    #[test]
+    fn accepts_shape_and_transform_keys() {
+        let scene_file = parse(
+            "[[objects]]\ntype = \"sphere\"\ncentre = [0.0, 0.0, 0.0]\nradius = 1.0\n\
+             material = \"white\"\ntranslate = [1.0, 0.0, 0.0]\n",
+        )
+        .unwrap();
+
+        assert_eq!(scene_file.objects[0].translate, Some([1.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn rejects_misspelled_object_keys() {
+        let err = parse(
+            "[[objects]]\ntype = \"sphere\"\ncentre = [0.0, 0.0, 0.0]\nradius = 1.0\n\
+             material = \"white\"\ntranslat = [1.0, 0.0, 0.0]\n",
+        )
+        .err()
+        .unwrap();
+
+        assert!(err.to_string().contains("translat"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_misspelled_keys_in_nested_objects() {
+        let result = parse(
+            "[[objects]]\ntype = \"bvh\"\nobjects = [{ type = \"sphere\", \
+             centre = [0.0, 0.0, 0.0], radius = 1.0, material = \"white\", rotatey = 45.0 }]\n",
+        );
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use std::{error::Error, fs, path::PathBuf, time::Instant};
+
+use crate::{
+    output::save_image,
+    renderer::{render, RenderSettings},
+    scene::{Scene, Shutter},
+    tonemap::ToneMapping,
+};
+
+/// Range of animation frames rendered to numbered images
+pub struct Sequence {
+    /// First and last frame, inclusive
+    pub first: u32,
+    pub last: u32,
+    /// Frames per unit of scene time
+    pub fps: f64,
+    /// Fraction of each frame the shutter is open for
+    pub shutter: f64,
+    pub output_dir: PathBuf,
+    /// Image format of each frame, e.g. "png" or "exr"
+    pub extension: String,
+    /// Render frames that already exist again instead of skipping them
+    pub overwrite: bool,
+}
+
+impl Sequence {
+    /// The shutter opens at the start of the frame and stays open for its fraction of it
+    pub fn shutter(&self, frame: u32) -> Shutter {
+        let open = frame as f64 / self.fps;
+
+        Shutter {
+            open,
+            close: open + self.shutter / self.fps,
+        }
+    }
+
+    pub fn frame_path(&self, frame: u32) -> PathBuf {
+        self.output_dir
+            .join(format!("frame_{:04}.{}", frame, self.extension))
+    }
+
+    /// Build the scene for each frame's shutter and render it, skipping frames already on
+    /// disk so an interrupted sequence picks up where it left off
+    pub fn render(
+        &self,
+        build_scene: impl Fn(Shutter) -> Scene,
+        scene_settings: impl Fn(&Scene) -> RenderSettings,
+        width: u32,
+        height: u32,
+        tone_mapping: &ToneMapping,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.first > self.last {
+            return Err(format!("First frame {} is after last {}", self.first, self.last).into());
+        }
+        if self.fps <= 0.0 || !self.fps.is_finite() {
+            return Err(format!("Frame rate must be positive and finite, not {}", self.fps).into());
+        }
+        if !(0.0..=1.0).contains(&self.shutter) {
+            return Err(format!("Shutter must be between 0 and 1, not {}", self.shutter).into());
+        }
+
+        fs::create_dir_all(&self.output_dir)?;
+
+        for frame in self.first..=self.last {
+            let path = self.frame_path(frame);
+            if path.exists() && !self.overwrite {
+                println!("Skipping frame {}, {} exists", frame, path.display());
+                continue;
+            }
+
+            let now = Instant::now();
+            let scene = build_scene(self.shutter(frame));
+            let linear = render(width, height, &scene, &scene_settings(&scene));
+
+            // Renamed into place once written, so a frame cut off mid-write isn't
+            // taken for a finished one and skipped on the next run
+            let partial = self
+                .output_dir
+                .join(format!("frame_{:04}.partial.{}", frame, self.extension));
+            save_image(
+                &partial.to_string_lossy(),
+                &linear,
+                width,
+                height,
+                tone_mapping,
+            )?;
+            fs::rename(&partial, &path)?;
+            println!(
+                "Rendered frame {} in {}",
+                frame,
+                now.elapsed().as_secs_f64()
+            );
+        }
+
+        Ok(())
+    }
+}
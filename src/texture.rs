@@ -1,10 +1,31 @@
+use std::{
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use glam::DVec4;
+use image::{ColorType, DynamicImage, ImageError};
+use serde::Deserialize;
+
 use crate::{
     perlin::Perlin,
-    vec3::{Color, Point3},
+    tonemap::srgb_eotf,
+    vec3::{Color, Point3, Vec3},
 };
 
 pub trait Texture: Sync + Send {
     fn value(&self, u: f64, v: f64, p: Point3) -> Color;
+
+    /// Value for a scalar parameter such as roughness, the mean of the channels
+    fn scalar(&self, u: f64, v: f64, p: Point3) -> f64 {
+        self.value(u, v, p).element_sum() / 3.0
+    }
+
+    /// Opacity, textures without an alpha channel are opaque
+    fn alpha(&self, _u: f64, _v: f64, _p: Point3) -> f64 {
+        1.0
+    }
 }
 pub struct SolidColor {
     pub color: Color,
@@ -22,6 +43,13 @@ impl SolidColor {
             color: Color::new(r, g, b),
         }
     }
+
+    /// Gray, for scalar parameters
+    pub fn splat(value: f64) -> Self {
+        SolidColor {
+            color: Color::splat(value),
+        }
+    }
 }
 
 pub struct CheckerTexture {
@@ -49,69 +77,458 @@ impl Texture for CheckerTexture {
     }
 }
 
+/// One channel of another texture scaled by a factor, for data packed into the channels
+/// of an image such as glTF's metallic-roughness maps
+pub struct ChannelTexture {
+    texture: Box<dyn Texture>,
+    channel: usize,
+    factor: f64,
+}
+
+impl ChannelTexture {
+    pub fn new(texture: Box<dyn Texture>, channel: usize, factor: f64) -> Self {
+        assert!(channel < 3, "Textures only have 3 channels");
+        ChannelTexture {
+            texture,
+            channel,
+            factor,
+        }
+    }
+}
+
+impl Texture for ChannelTexture {
+    fn value(&self, u: f64, v: f64, p: Point3) -> Color {
+        Color::splat(self.factor * self.texture.value(u, v, p)[self.channel])
+    }
+}
+
+/// Another texture multiplied by a color, such as glTF's base color and emissive factors
+pub struct TintedTexture {
+    texture: Box<dyn Texture>,
+    tint: Color,
+}
+
+impl TintedTexture {
+    pub fn new(texture: Box<dyn Texture>, tint: Color) -> Self {
+        TintedTexture { texture, tint }
+    }
+}
+
+impl Texture for TintedTexture {
+    fn value(&self, u: f64, v: f64, p: Point3) -> Color {
+        self.tint * self.texture.value(u, v, p)
+    }
+
+    fn alpha(&self, u: f64, v: f64, p: Point3) -> f64 {
+        self.texture.alpha(u, v, p)
+    }
+}
+
+/// What a `NoiseTexture` shows, each pattern is a gray level in [0, 1]
+#[derive(Clone, Copy, Debug)]
+pub enum NoisePattern {
+    /// Fractal Brownian motion, soft clouds
+    Fbm,
+    /// Absolute value of fBm, billows with dark creases
+    Turbulence,
+    /// Sharp bright ridges, like mountain ranges
+    Ridged,
+    /// Sine bands along `axis`, `frequency` radians per unit, warped by turbulence
+    Marble {
+        axis: Vec3,
+        frequency: f64,
+        turbulence: f64,
+    },
+    /// Rings around `axis` through the origin, `frequency` per unit of radius, warped by fBm
+    Wood {
+        axis: Vec3,
+        frequency: f64,
+        turbulence: f64,
+    },
+    /// Distance to the nearest Worley feature point, dark at the middle of each cell
+    Cells,
+    /// Difference of the distances to the nearest two feature points, dark along the
+    /// borders between cells
+    CellEdges,
+}
+
 pub struct NoiseTexture {
     noise: Perlin,
+    pattern: NoisePattern,
+    /// Frequency of the noise lookups
     scale: f64,
+    /// Octaves summed by the fractal patterns
+    octaves: i64,
 }
 
 impl NoiseTexture {
-    pub fn new_box(scale: f64) -> Box<Self> {
-        Box::new(NoiseTexture {
+    pub const DEFAULT_OCTAVES: i64 = 7;
+
+    pub fn new(pattern: NoisePattern) -> Self {
+        NoiseTexture {
             noise: Perlin::new(),
-            scale,
-        })
+            pattern,
+            scale: 1.0,
+            octaves: Self::DEFAULT_OCTAVES,
+        }
+    }
+
+    /// Marble banded along z
+    pub fn new_box(scale: f64) -> Box<Self> {
+        Box::new(Self::new(NoisePattern::Marble {
+            axis: Vec3::Z,
+            frequency: scale,
+            turbulence: 10.0,
+        }))
+    }
+
+    /// The same seed always gives the same pattern, otherwise it's random
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.noise = Perlin::seeded(seed);
+        self
+    }
+
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_octaves(mut self, octaves: i64) -> Self {
+        assert!(octaves > 0, "NoiseTexture needs an octave");
+        self.octaves = octaves;
+        self
+    }
+
+    fn gray(&self, p: Point3) -> f64 {
+        let q = self.scale * p;
+        let gray = match self.pattern {
+            NoisePattern::Fbm => 0.5 * (1.0 + self.noise.fbm(q, self.octaves)),
+            NoisePattern::Turbulence => self.noise.turb(q, self.octaves),
+            NoisePattern::Ridged => self.noise.ridged(q, self.octaves),
+            NoisePattern::Marble {
+                axis,
+                frequency,
+                turbulence,
+            } => {
+                let bands = frequency * axis.normalize().dot(p);
+                0.5 * (1.0 + (bands + turbulence * self.noise.turb(q, self.octaves)).sin())
+            }
+            NoisePattern::Wood {
+                axis,
+                frequency,
+                turbulence,
+            } => {
+                let radius = p.reject_from(axis).length();
+                (frequency * radius + turbulence * self.noise.fbm(q, self.octaves)).rem_euclid(1.0)
+            }
+            NoisePattern::Cells => self.noise.worley(q).0,
+            NoisePattern::CellEdges => {
+                let (nearest, second) = self.noise.worley(q);
+                second - nearest
+            }
+        };
+
+        gray.clamp(0.0, 1.0)
     }
 }
 
 impl Texture for NoiseTexture {
     fn value(&self, _u: f64, _v: f64, p: Point3) -> Color {
-        Color::new(1.0, 1.0, 1.0)
-            * 0.5
-            * (1.0 + (self.scale * p.z + 10.0 * self.noise.turb(p, 7)).sin())
+        Color::splat(self.gray(p))
     }
 }
 
-// TODO: Switch out and just use Image library?
+/// Colors another texture's scalar value by blending between stops, e.g. to tint a
+/// `NoiseTexture`. Values outside the stops take the nearest stop's color.
+pub struct ColorRamp {
+    input: Box<dyn Texture>,
+    /// Positions and colors, sorted by position
+    stops: Vec<(f64, Color)>,
+}
+
+impl ColorRamp {
+    pub fn new(input: Box<dyn Texture>, mut stops: Vec<(f64, Color)>) -> Self {
+        assert!(!stops.is_empty(), "ColorRamp needs a stop");
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        ColorRamp { input, stops }
+    }
+}
+
+impl Texture for ColorRamp {
+    fn value(&self, u: f64, v: f64, p: Point3) -> Color {
+        let t = self.input.scalar(u, v, p);
+        let next = self.stops.partition_point(|(position, _)| *position <= t);
+
+        match next {
+            0 => self.stops[0].1,
+            n if n == self.stops.len() => self.stops[n - 1].1,
+            n => {
+                let (t0, color0) = self.stops[n - 1];
+                let (t1, color1) = self.stops[n];
+                color0.lerp(color1, (t - t0) / (t1 - t0))
+            }
+        }
+    }
+}
+
+/// How texture coordinates outside [0, 1] are brought back onto the image
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WrapMode {
+    /// Tile the image
+    #[default]
+    Repeat,
+    /// Tile the image, flipping every other copy so edges meet seamlessly
+    Mirror,
+    /// Stretch the edge texels outwards
+    Clamp,
+}
+
+impl WrapMode {
+    /// Wrap a texel index onto an axis `size` texels long
+    fn wrap(self, index: i64, size: usize) -> usize {
+        let size = size as i64;
+        let index = match self {
+            WrapMode::Repeat => index.rem_euclid(size),
+            WrapMode::Mirror => {
+                let index = index.rem_euclid(2 * size);
+                match index < size {
+                    true => index,
+                    false => 2 * size - 1 - index,
+                }
+            }
+            WrapMode::Clamp => index.clamp(0, size - 1),
+        };
+
+        index as usize
+    }
+}
+
+/// How the color channels of an image are encoded. Alpha is always linear.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorSpace {
+    /// Gamma encoded, as almost all 8- and 16-bit color images are
+    #[default]
+    Srgb,
+    /// Stored as is, for data such as normal, roughness or height maps
+    Linear,
+}
+
+/// How an image is sampled between its texels
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    /// Blocky, the texel the point falls in
+    Nearest,
+    /// Smooth, blending the four nearest texels
+    #[default]
+    Bilinear,
+}
+
 pub struct ImageTexture {
-    data: Vec<u8>,
+    /// Linear RGBA, row by row from the top of the image
+    data: Vec<f32>,
     width: usize,
     height: usize,
-    bytes_per_pixel: usize,
+    wrap_u: WrapMode,
+    wrap_v: WrapMode,
+    filter: Filter,
+    /// Applied to texture coordinates before lookup, as `uv * scale + offset`
+    scale: (f64, f64),
+    offset: (f64, f64),
 }
 
 impl Texture for ImageTexture {
     fn value(&self, u: f64, v: f64, _p: Point3) -> Color {
-        // Clamp input texture coordinates to [0,1] x [1,0]
-        let u = u.clamp(0.0, 1.0);
-        let v = 1.0 - v.clamp(0.0, 1.0); // Flip v to image coordinates
+        self.sample(u, v).truncate()
+    }
 
-        // Clamp integer mapping, since actual coordinates should be less than 1.0
-        let mut i = (u * self.width as f64) as usize;
-        let mut j = (v * self.height as f64) as usize;
+    fn alpha(&self, u: f64, v: f64, _p: Point3) -> f64 {
+        self.sample(u, v).w
+    }
+}
 
-        if i >= self.width {
-            i = self.width - 1;
-        }
+impl ImageTexture {
+    /// 8-bit RGB or RGBA pixels, row by row from the top of the image
+    pub fn new(
+        data: Vec<u8>,
+        width: usize,
+        height: usize,
+        bytes_per_pixel: usize,
+        color_space: ColorSpace,
+    ) -> Self {
+        let data = data
+            .chunks_exact(bytes_per_pixel)
+            .flat_map(|pixel| {
+                let alpha = pixel.get(3).copied().unwrap_or(u8::MAX);
+                [pixel[0], pixel[1], pixel[2], alpha].map(|channel| channel as f32 / 255.0)
+            })
+            .collect();
 
-        if j >= self.height {
-            j = self.height - 1;
-        }
+        Self::from_rgba(data, width, height, color_space)
+    }
 
-        let color_scale = 1.0 / 255.0;
-        let pixel_index = i * self.bytes_per_pixel + j * self.width * self.bytes_per_pixel;
-        let pixel = &self.data[pixel_index..pixel_index + self.bytes_per_pixel];
+    /// Load any image the image crate can decode, with 8 or 16 bits per channel or floats
+    /// (.hdr, .exr)
+    pub fn open<P: AsRef<Path>>(path: P, color_space: ColorSpace) -> Result<Self, TextureError> {
+        let path = path.as_ref();
+        let image = image::open(path).map_err(|source| TextureError {
+            path: path.to_path_buf(),
+            source,
+        })?;
 
-        Color::new(pixel[0] as f64, pixel[1] as f64, pixel[2] as f64) * color_scale
+        Ok(Self::from_image(image, color_space))
     }
-}
 
-impl ImageTexture {
-    pub fn new(data: Vec<u8>, width: usize, height: usize, bytes_per_pixel: usize) -> Self {
+    /// Float images hold linear values already, so `color_space` only applies to integer ones
+    pub fn from_image(image: DynamicImage, color_space: ColorSpace) -> Self {
+        let width = image.width() as usize;
+        let height = image.height() as usize;
+        let color_space = match image.color() {
+            ColorType::Rgb32F | ColorType::Rgba32F => ColorSpace::Linear,
+            _ => color_space,
+        };
+
+        Self::from_rgba(image.into_rgba32f().into_raw(), width, height, color_space)
+    }
+
+    fn from_rgba(mut data: Vec<f32>, width: usize, height: usize, color_space: ColorSpace) -> Self {
+        assert_eq!(
+            data.len(),
+            4 * width * height,
+            "Image data doesn't match its size"
+        );
+
+        // Decoded once here rather than on every lookup, and before filtering so texels
+        // blend in linear light
+        if color_space == ColorSpace::Srgb {
+            for pixel in data.chunks_exact_mut(4) {
+                for channel in &mut pixel[..3] {
+                    *channel = srgb_eotf(*channel);
+                }
+            }
+        }
+
         ImageTexture {
             data,
             width,
             height,
-            bytes_per_pixel,
+            wrap_u: WrapMode::default(),
+            wrap_v: WrapMode::default(),
+            filter: Filter::default(),
+            scale: (1.0, 1.0),
+            offset: (0.0, 0.0),
         }
     }
+
+    pub fn with_wrap(mut self, wrap_u: WrapMode, wrap_v: WrapMode) -> Self {
+        self.wrap_u = wrap_u;
+        self.wrap_v = wrap_v;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Scale then offset texture coordinates, e.g. a scale of 4 tiles the image 4 times
+    pub fn with_transform(mut self, scale: (f64, f64), offset: (f64, f64)) -> Self {
+        self.scale = scale;
+        self.offset = offset;
+        self
+    }
+
+    /// Color and alpha at texture coordinates `u`, `v`
+    fn sample(&self, u: f64, v: f64) -> DVec4 {
+        let u = u * self.scale.0 + self.offset.0;
+        let v = v * self.scale.1 + self.offset.1;
+
+        // Position in texels, flipping v to image rows
+        let x = u * self.width as f64;
+        let y = (1.0 - v) * self.height as f64;
+
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                // Texel centres sit half way across each texel
+                let x = x - 0.5;
+                let y = y - 0.5;
+                let (i, j) = (x.floor() as i64, y.floor() as i64);
+                let (s, t) = (x - x.floor(), y - y.floor());
+
+                let top = self.texel(i, j).lerp(self.texel(i + 1, j), s);
+                let bottom = self.texel(i, j + 1).lerp(self.texel(i + 1, j + 1), s);
+                top.lerp(bottom, t)
+            }
+        }
+    }
+
+    fn texel(&self, i: i64, j: i64) -> DVec4 {
+        let i = self.wrap_u.wrap(i, self.width);
+        let j = self.wrap_v.wrap(j, self.height);
+
+        let index = 4 * (i + j * self.width);
+        let [r, g, b, a] = [0, 1, 2, 3].map(|channel| self.data[index + channel] as f64);
+        DVec4::new(r, g, b, a)
+    }
+}
+
+/// An image texture that couldn't be opened or decoded
+#[derive(Debug)]
+pub struct TextureError {
+    pub path: PathBuf,
+    pub source: ImageError,
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Failed to load texture {}: {}",
+            self.path.display(),
+            self.source
+        )
+    }
+}
+
+impl Error for TextureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrapped(mode: WrapMode) -> Vec<usize> {
+        (-4..8).map(|index| mode.wrap(index, 3)).collect()
+    }
+
+    #[test]
+    fn repeat_tiles_texels() {
+        assert_eq!(
+            wrapped(WrapMode::Repeat),
+            [2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1]
+        );
+    }
+
+    #[test]
+    fn mirror_reflects_at_each_edge() {
+        assert_eq!(
+            wrapped(WrapMode::Mirror),
+            [2, 2, 1, 0, 0, 1, 2, 2, 1, 0, 0, 1]
+        );
+    }
+
+    #[test]
+    fn clamp_repeats_edge_texels() {
+        assert_eq!(
+            wrapped(WrapMode::Clamp),
+            [0, 0, 0, 0, 0, 1, 2, 2, 2, 2, 2, 2]
+        );
+    }
 }
@@ -0,0 +1,96 @@
+use clap::ValueEnum;
+use rayon::prelude::*;
+
+/// Curve compressing unbounded linear radiance into [0, 1] for display
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum ToneMapOperator {
+    /// Clip anything brighter than 1
+    #[default]
+    Clamp,
+    /// x / (1 + x), never clips but flattens highlights
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve
+    Aces,
+}
+
+impl ToneMapOperator {
+    pub fn apply(self, x: f32) -> f32 {
+        let mapped = match self {
+            ToneMapOperator::Clamp => x,
+            ToneMapOperator::Reinhard => x / (1.0 + x),
+            ToneMapOperator::Aces => {
+                let (a, b, c, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+                (x * (a * x + b)) / (x * (c * x + d) + e)
+            }
+        };
+
+        mapped.clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ToneMapping {
+    pub operator: ToneMapOperator,
+    /// Exposure adjustment in stops, applied before the operator
+    pub exposure: f32,
+}
+
+impl ToneMapping {
+    /// Tone map a linear RGB framebuffer and encode it as sRGB RGBA8
+    pub fn encode_rgba8(&self, linear: &[f32]) -> Vec<u8> {
+        let scale = self.exposure.exp2();
+
+        linear
+            .par_chunks_exact(3)
+            .flat_map_iter(|pixel| {
+                let encode = |x: f32| {
+                    let display = srgb_oetf(self.operator.apply(x * scale));
+                    (display * 255.0).round() as u8
+                };
+
+                [encode(pixel[0]), encode(pixel[1]), encode(pixel[2]), 0xff]
+            })
+            .collect()
+    }
+}
+
+/// sRGB opto-electronic transfer function, linear [0, 1] to encoded [0, 1]
+pub fn srgb_oetf(linear: f32) -> f32 {
+    if linear <= 0.003_130_8 {
+        12.92 * linear
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Inverse of `srgb_oetf`, encoded [0, 1] to linear [0, 1]
+pub fn srgb_eotf(encoded: f32) -> f32 {
+    if encoded <= 0.040_45 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_round_trips_every_8_bit_level() {
+        for level in 0..=255u8 {
+            let encoded = level as f32 / 255.0;
+            assert!((srgb_oetf(srgb_eotf(encoded)) - encoded).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn srgb_matches_known_values() {
+        assert_eq!(srgb_eotf(0.0), 0.0);
+        assert!((srgb_eotf(1.0) - 1.0).abs() < 1e-6);
+        // Middle gray encodes to about 0.735
+        assert!((srgb_oetf(0.5) - 0.735_357).abs() < 1e-5);
+        // Both pieces meet at the breakpoint
+        assert!((srgb_oetf(0.003_130_8) - 0.040_45).abs() < 1e-5);
+    }
+}
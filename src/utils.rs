@@ -1,3 +1,5 @@
+use std::f64::consts::PI;
+
 use rand::random;
 
 use crate::vec3::Vec3;
@@ -51,3 +53,29 @@ pub fn random_in_unit_disc() -> Vec3 {
         }
     }
 }
+
+/// Random direction about the z-axis with a cosine weighted distribution
+pub fn random_cosine_direction() -> Vec3 {
+    let r1 = random_double(0.0, 1.0);
+    let r2 = random_double(0.0, 1.0);
+    let z = (1.0 - r2).sqrt();
+
+    let phi = 2.0 * PI * r1;
+    let x = phi.cos() * r2.sqrt();
+    let y = phi.sin() * r2.sqrt();
+
+    Vec3::new(x, y, z)
+}
+
+/// Random direction about the z-axis, uniformly within the cone subtended by a sphere
+pub fn random_to_sphere(radius: f64, distance_squared: f64) -> Vec3 {
+    let r1 = random_double(0.0, 1.0);
+    let r2 = random_double(0.0, 1.0);
+    let z = 1.0 + r2 * ((1.0 - radius * radius / distance_squared).sqrt() - 1.0);
+
+    let phi = 2.0 * PI * r1;
+    let x = phi.cos() * (1.0 - z * z).sqrt();
+    let y = phi.sin() * (1.0 - z * z).sqrt();
+
+    Vec3::new(x, y, z)
+}
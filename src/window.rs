@@ -1,21 +1,44 @@
 use pixels::{Pixels, SurfaceTexture};
 use std::error::Error;
+use std::sync::mpsc;
+use std::thread;
 use std::time::Instant;
 use winit::{
     dpi::LogicalSize,
     event::{Event, VirtualKeyCode},
-    event_loop::{ControlFlow, EventLoop},
+    event_loop::{ControlFlow, EventLoop, EventLoopBuilder},
     window::WindowBuilder,
 };
 use winit_input_helper::WinitInputHelper;
 
+use crate::camera::CameraSettings;
+
+const TITLE: &str = "Rusty Raytracing";
+
+/// Fraction of the distance to `lookat` travelled per second
+const MOVE_SPEED: f64 = 0.5;
+/// Degrees turned per pixel of mouse movement
+const LOOK_SENSITIVITY: f64 = 0.2;
+/// Degrees of field of view per scroll line
+const ZOOM_SPEED: f64 = 2.0;
+/// Fraction of the distance to `lookat` the aperture changes per key press
+const APERTURE_STEP: f64 = 0.01;
+/// Factor the focus distance changes by per key press
+const FOCUS_STEP: f64 = 1.1;
+
+/// Intermediate image produced by a progressive render
+pub struct Frame {
+    pub buffer: Vec<u8>,
+    pub samples: u32,
+}
+
 pub fn render_window(width: u32, height: u32, buffer: &[u8]) -> Result<(), Box<dyn Error>> {
     let mut input = WinitInputHelper::new();
     let event_loop = EventLoop::new();
     let window = {
         let size = LogicalSize::new(width, height);
         WindowBuilder::new()
-            .with_title("Rusty Raytracing")
+            .with_title(TITLE)
             .with_inner_size(size)
             .build(&event_loop)
             .unwrap()
@@ -42,7 +65,7 @@ pub fn render_window(width: u32, height: u32, buffer: &[u8]) -> Result<(), Box<d
         // Handle input events
         if input.update(&event) {
             // Close events
-                if input.key_pressed(VirtualKeyCode::Escape) || input.close_requested() {
+            if input.key_pressed(VirtualKeyCode::Escape) || input.close_requested() {
                 *control_flow = ControlFlow::Exit;
                 return;
             }
@@ -51,3 +74,145 @@ pub fn render_window(width: u32, height: u32, buffer: &[u8]) -> Result<(), Box<d
         }
     });
 }
+
+/// Show a render as it refines, `render_pass` is called on a background thread until it
+/// returns `None`, and each frame it returns is drawn as soon as it's ready.
+///
+/// The camera can be flown around with WASD (Q/E down/up), a left mouse drag to look,
+/// the scroll wheel to zoom, [ and ] for aperture and - and = for focus distance.
+/// `render_pass` is handed the new camera whenever it changes so it can start over.
+pub fn render_window_progressive<F>(
+    width: u32,
+    height: u32,
+    mut camera: CameraSettings,
+    mut render_pass: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(Option<CameraSettings>) -> Option<Frame> + Send + 'static,
+{
+    let mut input = WinitInputHelper::new();
+    let event_loop = EventLoopBuilder::<Frame>::with_user_event().build();
+    let window = {
+        let size = LogicalSize::new(width, height);
+        WindowBuilder::new()
+            .with_title(TITLE)
+            .with_inner_size(size)
+            .build(&event_loop)
+            .unwrap()
+    };
+
+    let mut pixels = {
+        let surface_texture = SurfaceTexture::new(width, height, &window);
+        Pixels::new(width, height, surface_texture)?
+    };
+
+    let proxy = event_loop.create_proxy();
+    let (camera_sender, camera_receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut new_camera = None;
+        loop {
+            // Only the latest camera matters
+            if let Some(latest) = camera_receiver.try_iter().last() {
+                new_camera = Some(latest);
+            }
+
+            match render_pass(new_camera.take()) {
+                Some(frame) => {
+                    // Window has closed
+                    if proxy.send_event(frame).is_err() {
+                        break;
+                    }
+                }
+                // Finished, wait until the camera moves again
+                None => match camera_receiver.recv() {
+                    Ok(latest) => new_camera = Some(latest),
+                    Err(_) => break,
+                },
+            }
+        }
+    });
+
+    let mut last_update = Instant::now();
+
+    event_loop.run(move |event, _, control_flow| {
+        // Draw the current frame
+        if let Event::RedrawRequested(_) = event {
+            if pixels.render().is_err() {
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+        }
+
+        // Show the latest pass
+        if let Event::UserEvent(frame) = &event {
+            pixels.frame_mut().copy_from_slice(&frame.buffer);
+            window.set_title(&format!("{} - {} spp", TITLE, frame.samples));
+            window.request_redraw();
+        }
+
+        // Handle input events
+        if input.update(&event) {
+            // Close events
+            if input.key_pressed(VirtualKeyCode::Escape) || input.close_requested() {
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+
+            let delta_time = last_update.elapsed().as_secs_f64();
+            last_update = Instant::now();
+
+            let moved_camera = navigate(&input, camera, delta_time);
+            if moved_camera != camera {
+                camera = moved_camera;
+                // Render thread only stops once the window has closed
+                camera_sender.send(camera).ok();
+            }
+
+            window.request_redraw();
+        }
+    });
+}
+
+fn navigate(input: &WinitInputHelper, camera: CameraSettings, delta_time: f64) -> CameraSettings {
+    let mut camera = camera;
+    let distance = (camera.lookat - camera.lookfrom).length();
+
+    let axis = |positive: VirtualKeyCode, negative: VirtualKeyCode| {
+        input.key_held(positive) as i32 as f64 - input.key_held(negative) as i32 as f64
+    };
+
+    let forward = axis(VirtualKeyCode::W, VirtualKeyCode::S);
+    let right = axis(VirtualKeyCode::D, VirtualKeyCode::A);
+    let up = axis(VirtualKeyCode::E, VirtualKeyCode::Q);
+    if forward != 0.0 || right != 0.0 || up != 0.0 {
+        let step = MOVE_SPEED * distance * delta_time;
+        camera = camera.translated(forward * step, right * step, up * step);
+    }
+
+    if input.mouse_held(0) {
+        let (dx, dy) = input.mouse_diff();
+        if dx != 0.0 || dy != 0.0 {
+            camera = camera.rotated(dx as f64 * LOOK_SENSITIVITY, -dy as f64 * LOOK_SENSITIVITY);
+        }
+    }
+
+    let scroll = input.scroll_diff() as f64;
+    if scroll != 0.0 {
+        camera.vertical_fov = (camera.vertical_fov - scroll * ZOOM_SPEED).clamp(1.0, 170.0);
+    }
+
+    if input.key_pressed(VirtualKeyCode::RBracket) {
+        camera.aperture += APERTURE_STEP * distance;
+    }
+    if input.key_pressed(VirtualKeyCode::LBracket) {
+        camera.aperture = (camera.aperture - APERTURE_STEP * distance).max(0.0);
+    }
+    if input.key_pressed(VirtualKeyCode::Equals) {
+        camera.focus_dist *= FOCUS_STEP;
+    }
+    if input.key_pressed(VirtualKeyCode::Minus) {
+        camera.focus_dist /= FOCUS_STEP;
+    }
+
+    camera
+}